@@ -1,7 +1,10 @@
+use intcode::symbolic::{Solution, Symbolic, SymbolicError, Target};
 use std::fs;
+use std::ops::RangeInclusive;
 use std::time::Instant;
 
 const PART_2_EXPECTED: usize = 19690720;
+const NOUN_VERB: RangeInclusive<usize> = 0..=99;
 
 fn main() {
     let now = Instant::now();
@@ -16,15 +19,50 @@ fn main() {
     let p1 = run_program(memory.clone(), 12, 2);
     println!("{}", p1);
 
+    // memory[0] is a polynomial of the noun and verb, solve it instead of trying them all.
+    // Searching is left for programs the solver does not support.
+    let p2 = match solve(&memory) {
+        Ok(p2) => p2,
+        Err(err) => {
+            println!("solver: {}, searching instead", err);
+            search(&memory)
+        }
+    };
+    match p2 {
+        Some(p2) => println!("{}", p2),
+        None => println!("no noun and verb give {}", PART_2_EXPECTED),
+    }
+
+    println!("Elapsed: {:.2?}", now.elapsed());
+}
+
+fn solve(memory: &[usize]) -> Result<Option<usize>, SymbolicError> {
+    let image = memory.iter().map(|v| *v as i64).collect::<Vec<i64>>();
+    let domain = *NOUN_VERB.start() as i64..=*NOUN_VERB.end() as i64;
+
+    let mut symbolic = Symbolic::new(&image);
+    symbolic.symbol_memory(1, "noun", domain.clone());
+    symbolic.symbol_memory(2, "verb", domain.clone());
+    let solutions = symbolic.solve(Target::Memory(0), PART_2_EXPECTED as i64)?;
+
+    // A symbol without a value does not affect the result, any value of its domain works
+    Ok(solutions.first().map(|Solution(values)| {
+        let noun = values[0].unwrap_or(*domain.start()) as usize;
+        let verb = values[1].unwrap_or(*domain.start()) as usize;
+        100 * noun + verb
+    }))
+}
+
+fn search(memory: &[usize]) -> Option<usize> {
     let mut nouns = Vec::with_capacity(100);
-    for v in 0..100 {
+    for v in NOUN_VERB {
         nouns.push((memory[v], v));
     }
-    nouns.sort_by(|a, b| a.0.cmp(&b.0));
+    nouns.sort_by_key(|(value, _)| *value);
     let verbs = nouns.clone();
 
     for (idx, (_, noun)) in nouns.iter().enumerate() {
-        let result = run_program(memory.clone(), *noun, verbs[0].1);
+        let result = run_program(memory.to_vec(), *noun, verbs[0].1);
         if result > PART_2_EXPECTED {
             nouns = nouns[0..idx].to_vec();
             break;
@@ -32,19 +70,18 @@ fn main() {
     }
 
     for (idx, (_, verb)) in nouns.iter().enumerate() {
-        let result = run_program(memory.clone(), nouns[0].1, *verb);
+        let result = run_program(memory.to_vec(), nouns[0].1, *verb);
         if result > PART_2_EXPECTED {
             nouns = nouns[0..idx].to_vec();
             break;
         }
     }
 
-    'outer: for (_, noun) in nouns.iter().rev() {
+    for (_, noun) in nouns.iter().rev() {
         for (_, verb) in verbs.iter() {
-            let result = run_program(memory.clone(), *noun, *verb);
+            let result = run_program(memory.to_vec(), *noun, *verb);
             if result == PART_2_EXPECTED {
-                println!("{}", 100 * noun + verb);
-                break 'outer;
+                return Some(100 * noun + verb);
             }
             if result > PART_2_EXPECTED {
                 break;
@@ -52,7 +89,7 @@ fn main() {
         }
     }
 
-    println!("Elapsed: {:.2?}", now.elapsed());
+    None
 }
 
 fn run_program(mut memory: Vec<usize>, noun: usize, verb: usize) -> usize {
@@ -0,0 +1,181 @@
+use std::str::FromStr;
+
+// Part 1, five amplifiers in a chain
+pub const CHAIN: &str = "\
+amps 5
+phases 0..=4
+edge 0 1
+edge 1 2
+edge 2 3
+edge 3 4
+entry 0
+exit 4
+";
+
+// Part 2, five amplifiers in a feedback loop
+pub const FEEDBACK_LOOP: &str = "\
+amps 5
+phases 5..=9
+edge 0 1
+edge 1 2
+edge 2 3
+edge 3 4
+edge 4 0
+entry 0
+exit 4
+";
+
+// Amplifier circuit, read from a config file with one `key value` line per setting:
+//
+//     amps 5          number of amplifiers, they are numbered from 0
+//     phases 5..=9    phase set of every amplifier
+//     phase 2 1,3     phase set of amplifier 2
+//     edge 4 0        output of amplifier 4 goes to amplifier 0
+//     entry 0         amplifier that receives the input signal 0
+//     exit 4          amplifier whose last output is the thrust
+//
+// A phase set is a comma separated list of values and inclusive ranges. Every amplifier
+// gets a phase from its set and no phase is used twice. An amplifier with several outgoing
+// edges sends every output to all of them. Empty lines and lines starting with # are ignored.
+#[derive(Debug, Clone)]
+pub struct Circuit {
+    pub amps: usize,
+    pub phases: Vec<Vec<i64>>,
+    pub edges: Vec<(usize, usize)>,
+    pub entry: usize,
+    pub exit: usize,
+}
+
+impl FromStr for Circuit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut amps = None;
+        let mut phases = None;
+        let mut amp_phases = Vec::new();
+        let mut edges = Vec::new();
+        let mut entry = None;
+        let mut exit = None;
+
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let err = |message: &str| format!("line {}: {}", idx + 1, message);
+            let mut words = line.split_whitespace();
+            let key = words.next().unwrap();
+            let values = words.collect::<Vec<&str>>();
+            let number = |idx: usize| -> Result<usize, String> {
+                let value = values.get(idx).ok_or_else(|| err("missing value"))?;
+                value
+                    .parse()
+                    .map_err(|_| err(&format!("not a number: {}", value)))
+            };
+
+            match (key, values.len()) {
+                ("amps", 1) => amps = Some(number(0)?),
+                ("phases", 1) => {
+                    phases = Some(parse_set(values[0]).ok_or_else(|| err("bad phase set"))?)
+                }
+                ("phase", 2) => amp_phases.push((
+                    number(0)?,
+                    parse_set(values[1]).ok_or_else(|| err("bad phase set"))?,
+                )),
+                ("edge", 2) => edges.push((number(0)?, number(1)?)),
+                ("entry", 1) => entry = Some(number(0)?),
+                ("exit", 1) => exit = Some(number(0)?),
+                ("amps" | "phases" | "phase" | "edge" | "entry" | "exit", _) => {
+                    return Err(err(&format!("wrong number of values for {}", key)))
+                }
+                _ => return Err(err(&format!("unknown setting {}", key))),
+            }
+        }
+
+        let amps = amps.ok_or("missing amps")?;
+        let mut circuit = Circuit {
+            amps,
+            phases: vec![phases.unwrap_or_default(); amps],
+            edges,
+            entry: entry.ok_or("missing entry")?,
+            exit: exit.ok_or("missing exit")?,
+        };
+
+        let check = |amp: usize| {
+            if amp < amps {
+                Ok(())
+            } else {
+                Err(format!("amplifier {} does not exist", amp))
+            }
+        };
+        for (amp, set) in amp_phases {
+            check(amp)?;
+            circuit.phases[amp] = set;
+        }
+        for (from, to) in circuit.edges.iter() {
+            check(*from)?;
+            check(*to)?;
+        }
+        check(circuit.entry)?;
+        check(circuit.exit)?;
+        if let Some(amp) = circuit.phases.iter().position(|set| set.is_empty()) {
+            return Err(format!("amplifier {} has no phases", amp));
+        }
+
+        Ok(circuit)
+    }
+}
+
+// Parses a phase set like 0,2,5..=9
+fn parse_set(set: &str) -> Option<Vec<i64>> {
+    let mut values = Vec::new();
+    for part in set.split(',') {
+        match part.split_once("..=") {
+            Some((start, end)) => values.extend(start.parse::<i64>().ok()?..=end.parse().ok()?),
+            None => values.push(part.parse().ok()?),
+        }
+    }
+    Some(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(config: &str) -> String {
+        config.parse::<Circuit>().unwrap_err()
+    }
+
+    #[test]
+    fn puzzle_circuits() {
+        let circuit: Circuit = FEEDBACK_LOOP.parse().unwrap();
+        assert_eq!(circuit.amps, 5);
+        assert_eq!(circuit.phases[4], [5, 6, 7, 8, 9]);
+        assert_eq!(circuit.edges.last(), Some(&(4, 0)));
+
+        let circuit: Circuit =
+            "amps 2\nphases 0..=1\n# comment\n\nphase 1 4,6..=7\nentry 0\nexit 1"
+                .parse()
+                .unwrap();
+        assert_eq!(circuit.phases, [vec![0, 1], vec![4, 6, 7]]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(error("amps 2\nspeed 3"), "line 2: unknown setting speed");
+        assert_eq!(error("amps 2 3"), "line 1: wrong number of values for amps");
+        assert_eq!(error("amps two"), "line 1: not a number: two");
+        assert_eq!(error("phases 1..=x"), "line 1: bad phase set");
+        assert_eq!(error("entry 0\nexit 0"), "missing amps");
+        assert_eq!(error("amps 1\nphases 0\nexit 0"), "missing entry");
+        assert_eq!(
+            error("amps 2\nphases 0,1\nedge 0 2\nentry 0\nexit 1"),
+            "amplifier 2 does not exist"
+        );
+        assert_eq!(
+            error("amps 2\nphase 0 1\nentry 0\nexit 1"),
+            "amplifier 1 has no phases"
+        );
+    }
+}
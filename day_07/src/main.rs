@@ -1,300 +1,222 @@
+mod circuit;
+
+use circuit::Circuit;
+use intcode::scheduler::{Outcome, Scheduler};
+use intcode::Program;
+use std::env;
 use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 use std::time::Instant;
 
-enum Inst {
-    Add,
-    Multiply,
-    Input,
-    Output,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    Halt,
-}
-
-enum Interupt {
-    Input,
-    Output(i32),
-    Halt,
-}
-
-struct Opcode {
-    inst: Inst,
-    flags: u8,
-}
-
-impl Opcode {
-    fn from_code(mut code: i32) -> Self {
-        let inst = match code % 100 {
-            1 => Inst::Add,
-            2 => Inst::Multiply,
-            3 => Inst::Input,
-            4 => Inst::Output,
-            5 => Inst::JumpIfTrue,
-            6 => Inst::JumpIfFalse,
-            7 => Inst::LessThan,
-            8 => Inst::Equals,
-            99 => Inst::Halt,
-            _ => panic!("unknown opcode {}", code),
-        };
-
-        let mut flags = 0;
-        code = code / 100;
-        if code % 10 == 1 {
-            flags |= 1;
-        };
-        code = code / 10;
-        if code % 10 == 1 {
-            flags |= 2;
-        };
-        code = code / 10;
-        if code % 10 == 1 {
-            flags |= 4;
-        };
-
-        Self { inst, flags }
-    }
-}
-
 fn main() {
     let now = Instant::now();
     let puzzle = fs::read_to_string("./puzzle.txt").unwrap();
 
     let mut runtime = Runtime::default();
     for code in puzzle.split(',') {
-        let code: i32 = if let Ok(v) = code.parse() {
+        let code: i64 = if let Ok(v) = code.parse() {
             v
         } else {
-            code.split('\n').nth(0).unwrap().parse().unwrap()
+            code.split('\n').next().unwrap().parse().unwrap()
         };
         runtime.source_memory.push(code);
     }
 
-    let max_thrust = runtime.find_best_thrust_phase_settings_p1(0, 0, &mut [0, 0, 0, 0, 0]);
-    println!("p1: {}", max_thrust);
+    // Circuit config files can be passed as arguments, by default both puzzle parts are solved
+    let mut circuits = Vec::new();
+    for path in env::args().skip(1) {
+        circuits.push((path.clone(), fs::read_to_string(&path).unwrap()));
+    }
+    if circuits.is_empty() {
+        circuits.push(("p1".to_string(), circuit::CHAIN.to_string()));
+        circuits.push(("p2".to_string(), circuit::FEEDBACK_LOOP.to_string()));
+    }
 
-    let max_thrust = runtime.find_best_thrust_phase_settings_p2(0, 0, &mut [0, 0, 0, 0, 0]);
-    println!("p2: {}", max_thrust);
+    for (name, config) in circuits {
+        let circuit: Circuit = match config.parse() {
+            Ok(circuit) => circuit,
+            Err(err) => panic!("{}: {}", name, err),
+        };
+        match runtime.best_thrust(&circuit) {
+            Some((thrust, phases)) => println!("{}: {} (phases: {:?})", name, thrust, phases),
+            None => println!("{}: no valid phase settings", name),
+        }
+    }
 
     println!("Elapsed: {:.2?}", now.elapsed());
 }
 
 #[derive(Default)]
 struct Runtime {
-    source_memory: Vec<i32>,
-    ampifiers: [Program; 5],
+    source_memory: Vec<i64>,
 }
 
 impl Runtime {
-    fn find_best_thrust_phase_settings_p1(
-        &mut self,
-        offset: usize,
-        flags: u8,
-        memory: &mut [i32; 5],
-    ) -> i32 {
-        let mut max_thrust = 0;
-
-        for setting in 0..=4 {
-            let flag = 1 << setting;
-            if flags & flag != 0 {
-                continue;
-            }
-
-            let new_flags = flags | flag;
-            memory[offset] = setting;
+    // Tries every phase assignment and returns the highest thrust with its phases.
+    // On equal thrust the first assignment in search order wins, no matter how the work was
+    // spread over the threads.
+    fn best_thrust(&self, circuit: &Circuit) -> Option<(i64, Vec<i64>)> {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+
+        // Split the search on the phases of the first amplifiers, enough parts to keep every
+        // thread busy. The parts are in the order the serial search would visit them.
+        let mut prefixes = vec![Vec::new()];
+        let mut depth = 0;
+        while depth < circuit.amps && prefixes.len() < threads * 8 {
+            prefixes = prefixes
+                .iter()
+                .flat_map(|prefix| {
+                    circuit.phases[depth]
+                        .iter()
+                        .filter(|phase| !prefix.contains(*phase))
+                        .map(|phase| {
+                            let mut next = prefix.clone();
+                            next.push(*phase);
+                            next
+                        })
+                })
+                .collect();
+            depth += 1;
+        }
 
-            let thrust = if offset == 4 {
-                self.calculate_thrust(memory)
-            } else {
-                self.find_best_thrust_phase_settings_p1(offset + 1, new_flags, memory)
-            };
-            if thrust > max_thrust {
-                max_thrust = thrust;
+        let next_prefix = AtomicUsize::new(0);
+        let mut results = thread::scope(|scope| {
+            let workers = (0..threads.min(prefixes.len()))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = Vec::new();
+                        loop {
+                            let idx = next_prefix.fetch_add(1, Ordering::Relaxed);
+                            let Some(prefix) = prefixes.get(idx) else {
+                                return results;
+                            };
+
+                            let mut best = None;
+                            let mut phases = prefix.clone();
+                            self.search(circuit, &mut phases, &mut best);
+                            results.push((idx, best));
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        results.sort_by_key(|(idx, _)| *idx);
+        let mut best: Option<(i64, Vec<i64>)> = None;
+        for (thrust, phases) in results.into_iter().filter_map(|(_, result)| result) {
+            if best
+                .as_ref()
+                .is_none_or(|(max_thrust, _)| thrust > *max_thrust)
+            {
+                best = Some((thrust, phases));
             }
         }
-
-        max_thrust
+        best
     }
-    fn find_best_thrust_phase_settings_p2(
-        &mut self,
-        offset: usize,
-        flags: u8,
-        memory: &mut [i32; 5],
-    ) -> i32 {
-        let mut max_thrust = 0;
+    fn search(&self, circuit: &Circuit, phases: &mut Vec<i64>, best: &mut Option<(i64, Vec<i64>)>) {
+        if phases.len() == circuit.amps {
+            let thrust = self.calculate_thrust(circuit, phases);
+            if best
+                .as_ref()
+                .is_none_or(|(max_thrust, _)| thrust > *max_thrust)
+            {
+                *best = Some((thrust, phases.clone()));
+            }
+            return;
+        }
 
-        for setting in 5..=9 {
-            let flag = 1 << (setting - 5);
-            if flags & flag != 0 {
+        for phase in circuit.phases[phases.len()].iter() {
+            if phases.contains(phase) {
                 continue;
             }
 
-            let new_flags = flags | flag;
-            memory[offset] = setting as i32;
-
-            let thrust = if offset == 4 {
-                self.calculate_thrust(memory)
-            } else {
-                self.find_best_thrust_phase_settings_p2(offset + 1, new_flags, memory)
-            };
-            if thrust > max_thrust {
-                max_thrust = thrust;
-            }
+            phases.push(*phase);
+            self.search(circuit, phases, best);
+            phases.pop();
         }
-
-        max_thrust
     }
-
-    fn calculate_thrust(&mut self, phase_settings: &mut [i32; 5]) -> i32 {
-        self.reset_ampifiers();
-
-        let mut inputs = Vec::new();
-        let mut last_out = 0;
-        for (idx, amp) in self.ampifiers.iter_mut().enumerate() {
-            inputs.clear();
-            inputs.push(phase_settings[idx]);
-            inputs.push(last_out);
-
-            match amp.start(&mut inputs) {
-                Interupt::Input => {
-                    panic!("this should never be called");
-                }
-                Interupt::Output(out) => {
-                    last_out = out;
-                }
-                Interupt::Halt => {
-                    return last_out;
-                }
+    fn calculate_thrust(&self, circuit: &Circuit, phases: &[i64]) -> i64 {
+        // Every amplifier reads from the queue named after itself
+        let names = (0..circuit.amps)
+            .map(|amp| amp.to_string())
+            .collect::<Vec<String>>();
+
+        let mut scheduler = Scheduler::default();
+        for (amp, phase) in phases.iter().enumerate() {
+            let mut program = Program::default();
+            program.reset(self.source_memory.clone());
+
+            let mut outputs = circuit
+                .edges
+                .iter()
+                .filter(|(from, _)| *from == amp)
+                .map(|(_, to)| names[*to].as_str())
+                .collect::<Vec<&str>>();
+            if amp == circuit.exit {
+                outputs.push("thrust");
             }
-        }
-
-        loop {
-            for amp in self.ampifiers.iter_mut() {
-                inputs.clear();
-                inputs.push(last_out);
 
-                match amp.start(&mut inputs) {
-                    Interupt::Input => {
-                        panic!("this should never be called");
-                    }
-                    Interupt::Output(out) => {
-                        last_out = out;
-                    }
-                    Interupt::Halt => {
-                        return last_out;
-                    }
-                }
-            }
+            scheduler.add(&names[amp], program, &names[amp], &outputs);
+            scheduler.push(&names[amp], *phase);
         }
-    }
-    fn reset_ampifiers(&mut self) {
-        for amp in self.ampifiers.iter_mut() {
-            amp.memory = self.source_memory.clone();
-            amp.instruction_pointer = 0;
+        scheduler.push(&names[circuit.entry], 0);
+
+        match scheduler.run() {
+            // Amplifiers that keep waiting for input after the signal passed are fine
+            Outcome::Halted { .. } | Outcome::Deadlock { .. } => {}
+            Outcome::Fault { machine, fault } => panic!("amplifier {}: {}", machine, fault),
+            outcome => unreachable!("no watchpoints or budgets are set: {:?}", outcome),
         }
+
+        scheduler
+            .queue("thrust")
+            .and_then(|thrust| thrust.back().copied())
+            .unwrap_or(0)
     }
 }
 
-#[derive(Clone, Default)]
-struct Program {
-    memory: Vec<i32>,
-    instruction_pointer: usize,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl Program {
-    fn a(&self, opcode: &Opcode) -> i32 {
-        let raw_value = self.memory[self.instruction_pointer + 1];
-        let parameter_mode = opcode.flags & 1;
-        match parameter_mode {
-            0 /* Ptr to value */ => self.memory[raw_value as usize],
-            1 /* Litteral value */ => raw_value,
-            _ => panic!("unknown flag"),
-        }
-    }
-    fn b(&self, opcode: &Opcode) -> i32 {
-        let raw_value = self.memory[self.instruction_pointer + 2];
-        let parameter_mode = opcode.flags >> 1 & 1;
-        match parameter_mode {
-            0 /* Ptr to value */ => self.memory[raw_value as usize],
-            1 /* Litteral value */ => raw_value,
-            _ => panic!("unknown flag"),
+    fn runtime(program: &str) -> Runtime {
+        Runtime {
+            source_memory: intcode::parse(program),
         }
     }
-    fn start(&mut self, input: &mut Vec<i32>) -> Interupt {
-        loop {
-            let opcode = Opcode::from_code(self.memory[self.instruction_pointer]);
-
-            match opcode.inst {
-                Inst::Add => {
-                    let c_ptr = self.memory[self.instruction_pointer + 3];
-
-                    self.memory[c_ptr as usize] = self.a(&opcode) + self.b(&opcode);
-                    self.instruction_pointer += 4;
-                }
-                Inst::Multiply => {
-                    let c_ptr = self.memory[self.instruction_pointer + 3];
-
-                    self.memory[c_ptr as usize] = self.a(&opcode) * self.b(&opcode);
-                    self.instruction_pointer += 4;
-                }
-                Inst::Input => {
-                    let a_ptr = self.memory[self.instruction_pointer + 1];
 
-                    if input.len() == 0 {
-                        return Interupt::Input;
-                    }
-
-                    self.memory[a_ptr as usize] = input.remove(0);
-                    self.instruction_pointer += 2;
-                }
-                Inst::Output => {
-                    let a_ptr = self.memory[self.instruction_pointer + 1];
-
-                    self.instruction_pointer += 2;
-                    return Interupt::Output(self.memory[a_ptr as usize]);
-                }
-                Inst::JumpIfTrue => {
-                    let a = self.a(&opcode);
-                    let b = self.b(&opcode);
-
-                    if a != 0 {
-                        self.instruction_pointer = b as usize;
-                    } else {
-                        self.instruction_pointer += 3;
-                    }
-                }
-                Inst::JumpIfFalse => {
-                    if self.a(&opcode) == 0 {
-                        self.instruction_pointer = self.b(&opcode) as usize;
-                    } else {
-                        self.instruction_pointer += 3;
-                    }
-                }
-                Inst::LessThan => {
-                    let c_ptr = self.memory[self.instruction_pointer + 3];
+    #[test]
+    fn examples() {
+        let chain = circuit::CHAIN.parse().unwrap();
+        assert_eq!(
+            runtime("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0").best_thrust(&chain),
+            Some((43210, vec![4, 3, 2, 1, 0]))
+        );
+
+        let feedback_loop = circuit::FEEDBACK_LOOP.parse().unwrap();
+        let program = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,\
+                       1005,28,6,99,0,0,5";
+        assert_eq!(
+            runtime(program).best_thrust(&feedback_loop),
+            Some((139629729, vec![9, 8, 7, 6, 5]))
+        );
+    }
 
-                    self.memory[c_ptr as usize] = if self.a(&opcode) < self.b(&opcode) {
-                        1
-                    } else {
-                        0
-                    };
-                    self.instruction_pointer += 4;
-                }
-                Inst::Equals => {
-                    let c_ptr = self.memory[self.instruction_pointer + 3];
+    #[test]
+    fn ties_match_serial_search() {
+        // Every phase assignment gives a thrust of 7
+        let runtime = runtime("3,7,3,7,104,7,99,0");
+        let circuit = circuit::CHAIN.parse().unwrap();
 
-                    self.memory[c_ptr as usize] = if self.a(&opcode) == self.b(&opcode) {
-                        1
-                    } else {
-                        0
-                    };
-                    self.instruction_pointer += 4;
-                }
-                Inst::Halt => {
-                    return Interupt::Halt;
-                }
-            }
-        }
+        let mut serial = None;
+        runtime.search(&circuit, &mut Vec::new(), &mut serial);
+        assert_eq!(serial, Some((7, vec![0, 1, 2, 3, 4])));
+        assert_eq!(runtime.best_thrust(&circuit), serial);
     }
 }
@@ -1,11 +1,12 @@
-mod vm;
-
 use crossterm::{cursor, event, execute, style, style::Print, terminal};
+use intcode::{Flag, Frame, Io, Program};
 use std::fs;
 use std::io::stdout;
 use std::thread::sleep;
 use std::time::{Duration, Instant};
-use vm::{Interupt, Program};
+
+// Inputs of the last debug game, replay with: cargo run --bin replay -- ../day_13/session.txt
+const SESSION: &str = "./session.txt";
 
 fn main() {
     let now = Instant::now();
@@ -17,7 +18,7 @@ fn main() {
         let code: i64 = if let Ok(v) = code.parse() {
             v
         } else {
-            code.split('\n').nth(0).unwrap().parse().unwrap()
+            code.split('\n').next().unwrap().parse().unwrap()
         };
         runtime.source_memory.push(code);
     }
@@ -55,17 +56,20 @@ impl Runtime {
 
         let mut blocks = 0;
         let mut input = Vec::new();
-        let mut out_nr = 0;
         loop {
-            match self.program.start(&mut input) {
-                Interupt::Halt => break,
-                Interupt::Input => panic!("Input required"),
-                Interupt::Output(v) => {
-                    if out_nr == 2 && v == 2 {
+            match self.program.run_until_frame::<3>(&mut input) {
+                Frame::Complete([_, _, tile]) => {
+                    if tile == 2 {
                         blocks += 1;
                     }
-                    out_nr = (out_nr + 1) % 3;
                 }
+                Frame::Truncated(_) => panic!("Incomplete tile"),
+                Frame::Interupt(interupt) => match interupt.expect_io() {
+                    Io::Halt => break,
+                    Io::Fault(fault) => panic!("{}", fault),
+                    Io::Input => panic!("Input required"),
+                    Io::Output(_) => unreachable!("output is framed"),
+                },
             }
         }
 
@@ -74,6 +78,9 @@ impl Runtime {
     fn play(&mut self, debug: bool) -> i64 {
         self.reset();
         self.program.memory[0] = 2;
+        if debug {
+            self.program.start_session();
+        }
 
         let mut map: Vec<Vec<Tail>> = Vec::new();
         if debug {
@@ -84,10 +91,6 @@ impl Runtime {
 
         let mut input = Vec::new();
 
-        let mut x = 0;
-        let mut y = 0;
-
-        let mut out_nr = 0;
         let mut score = 0;
 
         if debug {
@@ -106,124 +109,120 @@ impl Runtime {
         let mut ball_pos = 0;
 
         'outer: loop {
-            let interupt = self.program.start(&mut input);
+            let frame = self.program.run_until_frame::<3>(&mut input);
 
-            match interupt {
-                Interupt::Halt => break,
-                Interupt::Input => {
-                    if debug {
-                        // Print the map to the screen
-                        for (y, row) in map.iter().enumerate() {
-                            let mut row_chars = Vec::new();
-                            for (x, cell) in row.iter().enumerate() {
-                                match cell {
-                                    Tail::Empty => row_chars.push(' '),
-                                    Tail::Wall => row_chars.push('#'),
-                                    Tail::Block => row_chars.push('x'),
-                                    Tail::Paddle => {
-                                        paddle_pos = x;
-                                        row_chars.push('-')
-                                    }
-                                    Tail::Ball => {
-                                        ball_pos = x;
-                                        row_chars.push('o')
-                                    }
-                                };
-                            }
+            match frame {
+                Frame::Truncated(_) => panic!("Incomplete tile"),
+                Frame::Interupt(interupt) => match interupt.expect_io() {
+                    Io::Halt => break,
+                    Io::Fault(fault) => panic!("{}", fault),
+                    Io::Output(_) => unreachable!("output is framed"),
+                    Io::Input => {
+                        if debug {
+                            // Print the map to the screen
+                            for (y, row) in map.iter().enumerate() {
+                                let mut row_chars = Vec::new();
+                                for (x, cell) in row.iter().enumerate() {
+                                    match cell {
+                                        Tail::Empty => row_chars.push(' '),
+                                        Tail::Wall => row_chars.push('#'),
+                                        Tail::Block => row_chars.push('x'),
+                                        Tail::Paddle => {
+                                            paddle_pos = x;
+                                            row_chars.push('-')
+                                        }
+                                        Tail::Ball => {
+                                            ball_pos = x;
+                                            row_chars.push('o')
+                                        }
+                                    };
+                                }
 
+                                _ = execute!(
+                                    stdout(),
+                                    cursor::MoveTo(0, y as u16),
+                                    Print(row_chars.iter().collect::<String>())
+                                );
+                            }
                             _ = execute!(
                                 stdout(),
-                                cursor::MoveTo(0, y as u16),
-                                Print(row_chars.iter().collect::<String>())
+                                cursor::MoveTo(2, map.len() as u16 + 1),
+                                Print(format!("Score: {}", score)),
                             );
-                        }
-                        _ = execute!(
-                            stdout(),
-                            cursor::MoveTo(2, map.len() as u16 + 1),
-                            Print(format!("Score: {}", score)),
-                        );
 
-                        let now = Instant::now();
-                        let duration = 50;
-                        if event::poll(Duration::from_millis(duration)).unwrap() {
-                            if let event::Event::Key(event) = event::read().unwrap() {
-                                match event.code {
-                                    event::KeyCode::Esc => break 'outer,
-                                    event::KeyCode::Char('q') => break 'outer,
-                                    event::KeyCode::Char('c')
-                                        if event.modifiers & event::KeyModifiers::CONTROL
-                                            == event::KeyModifiers::CONTROL =>
-                                    {
-                                        break 'outer
+                            let now = Instant::now();
+                            let duration = 50;
+                            if event::poll(Duration::from_millis(duration)).unwrap() {
+                                if let event::Event::Key(event) = event::read().unwrap() {
+                                    match event.code {
+                                        event::KeyCode::Esc => break 'outer,
+                                        event::KeyCode::Char('q') => break 'outer,
+                                        event::KeyCode::Char('c')
+                                            if event.modifiers & event::KeyModifiers::CONTROL
+                                                == event::KeyModifiers::CONTROL =>
+                                        {
+                                            break 'outer
+                                        }
+                                        _ => {} // ignore
                                     }
-                                    _ => {} // ignore
                                 }
                             }
+
+                            if now.elapsed() < Duration::from_millis(duration) {
+                                sleep(Duration::from_millis(duration) - now.elapsed());
+                            }
                         }
 
-                        if now.elapsed() < Duration::from_millis(duration) {
-                            sleep(Duration::from_millis(duration) - now.elapsed());
+                        if paddle_pos < ball_pos {
+                            input.push(1);
+                        } else if paddle_pos > ball_pos {
+                            input.push(-1);
+                        } else {
+                            input.push(0);
                         }
                     }
-
-                    if paddle_pos < ball_pos {
-                        input.push(1);
-                    } else if paddle_pos > ball_pos {
-                        input.push(-1);
+                },
+                Frame::Complete([x, y, v]) => {
+                    if debug {
+                        match v {
+                            v if x == -1 && y == 0 => {
+                                score = v;
+                            }
+                            0 => map[y as usize][x as usize] = Tail::Empty,
+                            1 => map[y as usize][x as usize] = Tail::Wall,
+                            2 => map[y as usize][x as usize] = Tail::Block,
+                            3 => {
+                                paddle_pos = x as usize;
+                                map[y as usize][x as usize] = Tail::Paddle;
+                            }
+                            4 => {
+                                ball_pos = x as usize;
+                                map[y as usize][x as usize] = Tail::Ball;
+                            }
+                            _ => panic!("Unknown tile, v:{}, x:{}, y:{}", v, x, y),
+                        };
                     } else {
-                        input.push(0);
-                    }
-                }
-                Interupt::Output(v) => {
-                    match out_nr {
-                        0 => {
-                            x = v;
-                        }
-                        1 => {
-                            y = v;
-                        }
-                        2 => {
-                            if debug {
-                                match v {
-                                    v if x == -1 && y == 0 => {
-                                        score = v;
-                                    }
-                                    0 => map[y as usize][x as usize] = Tail::Empty,
-                                    1 => map[y as usize][x as usize] = Tail::Wall,
-                                    2 => map[y as usize][x as usize] = Tail::Block,
-                                    3 => {
-                                        paddle_pos = x as usize;
-                                        map[y as usize][x as usize] = Tail::Paddle;
-                                    }
-                                    4 => {
-                                        ball_pos = x as usize;
-                                        map[y as usize][x as usize] = Tail::Ball;
-                                    }
-                                    _ => panic!("Unknown tile, v:{}, x:{}, y:{}", v, x, y),
-                                };
-                            } else {
-                                match v {
-                                    v if x == -1 && y == 0 => {
-                                        score = v;
-                                    }
-                                    3 => {
-                                        paddle_pos = x as usize;
-                                    }
-                                    4 => {
-                                        ball_pos = x as usize;
-                                    }
-                                    _ => {} // ignore
-                                };
+                        match v {
+                            v if x == -1 && y == 0 => {
+                                score = v;
                             }
-                        }
-                        _ => unreachable!(),
+                            3 => {
+                                paddle_pos = x as usize;
+                            }
+                            4 => {
+                                ball_pos = x as usize;
+                            }
+                            _ => {} // ignore
+                        };
                     }
-
-                    out_nr = (out_nr + 1) % 3;
                 }
             }
         }
 
+        if let Some(session) = self.program.stop_session() {
+            session.save(SESSION).unwrap();
+        }
+
         if debug {
             terminal::disable_raw_mode().unwrap();
             _ = execute!(
@@ -246,12 +245,12 @@ impl Runtime {
             let val = &self.program.memory[idx];
 
             let color = match flag {
-                vm::Flag::Unflagged => style::Color::DarkGrey,
-                vm::Flag::Inst => style::Color::Green,
-                vm::Flag::Param => style::Color::Blue,
-                vm::Flag::ReadWrite => style::Color::Red,
-                vm::Flag::Read => style::Color::Cyan,
-                vm::Flag::Write => style::Color::White,
+                Flag::Unflagged => style::Color::DarkGrey,
+                Flag::Inst => style::Color::Green,
+                Flag::Param => style::Color::Blue,
+                Flag::ReadWrite => style::Color::Red,
+                Flag::Read => style::Color::Cyan,
+                Flag::Write => style::Color::White,
             };
 
             _ = execute!(
@@ -1,13 +1,15 @@
-mod vm;
-
 use crossterm::{cursor, event, execute, style::Print, terminal};
+use intcode::{Interupt, Io, Program};
 use std::fs;
 use std::io::stdout;
 use std::time::Instant;
-use vm::{Interupt, Program};
 
 const DEBUG: bool = false;
 const MAP_SIZE: usize = 50;
+// Instructions the droid program may use to answer a single move
+const MOVE_BUDGET: u64 = 1_000_000;
+// Moves of the last debug run, replay with: cargo run --bin replay -- ../day_15/session.txt
+const SESSION: &str = "./session.txt";
 
 fn main() {
     let now = Instant::now();
@@ -19,7 +21,7 @@ fn main() {
         let code: i64 = if let Ok(v) = code.parse() {
             v
         } else {
-            code.split('\n').nth(0).unwrap().parse().unwrap()
+            code.split('\n').next().unwrap().parse().unwrap()
         };
         runtime.source_memory.push(code);
     }
@@ -170,7 +172,7 @@ impl Runtime {
                 }
             }
 
-            options.sort_by(|a, b| a.1.cmp(&b.1));
+            options.sort_by_key(|a| a.1);
             for option in &options {
                 if let Some(path) = self.path_to_location(self.location, option.0) {
                     let mut walkable = false;
@@ -205,7 +207,7 @@ impl Runtime {
                 }
             }
 
-            options.sort_by(|a, b| a.1.cmp(&b.1));
+            options.sort_by_key(|a| a.1);
             for option in &options {
                 if let Some(path) = self.path_to_location(self.location, option.0) {
                     let mut walkable = false;
@@ -227,6 +229,10 @@ impl Runtime {
             break;
         }
 
+        if let Some(session) = self.program.stop_session() {
+            session.save(SESSION).unwrap();
+        }
+
         if DEBUG {
             terminal::disable_raw_mode().unwrap();
             _ = execute!(
@@ -273,10 +279,15 @@ impl Runtime {
     }
     fn walk(&mut self, direction: &DroidDirection) -> bool {
         let mut input = vec![direction.to_num()];
-        match self.program.run(&mut input) {
-            Interupt::Halt => panic!("Halted"),
-            Interupt::Input => panic!("Expected output, got input"),
-            Interupt::Output(v) => {
+        match self.program.run(&mut input).io() {
+            Ok(Io::Halt) => panic!("Halted"),
+            Ok(Io::Fault(fault)) => panic!("{}", fault),
+            Ok(Io::Input) => panic!("Expected output, got input"),
+            Err(Interupt::BudgetExhausted) => {
+                panic!("Droid did not answer within {} instructions", MOVE_BUDGET)
+            }
+            Err(interupt) => unreachable!("no watchpoints are set: {:?}", interupt),
+            Ok(Io::Output(v)) => {
                 let new_location = direction.move_location(self.location);
                 let x = new_location.0;
                 let y = new_location.1;
@@ -332,6 +343,10 @@ impl Runtime {
     }
     fn reset(&mut self) {
         self.program.reset(self.source_memory.clone());
+        self.program.set_run_budget(MOVE_BUDGET);
+        if DEBUG {
+            self.program.start_session();
+        }
     }
 }
 
@@ -0,0 +1,35 @@
+use intcode::{Interupt, Program};
+use std::time::Instant;
+
+// Regenerate with:
+// cargo run --bin transpile -- benches/transpiled/program.txt Compiled > benches/transpiled/compiled.rs
+#[rustfmt::skip]
+#[path = "transpiled/compiled.rs"]
+mod compiled;
+
+// Iterations of the summing loop in program.txt
+const LOOPS: i64 = 20_000_000;
+
+fn main() {
+    let image = intcode::parse(include_str!("transpiled/program.txt"));
+
+    let now = Instant::now();
+    let mut program = Program::default();
+    program.reset(image);
+    let expected = program.run(&mut vec![LOOPS]);
+    let interpreted = now.elapsed();
+
+    let now = Instant::now();
+    let mut compiled = compiled::Compiled::default();
+    let actual = compiled.run(&mut vec![LOOPS]);
+    let transpiled = now.elapsed();
+
+    assert_eq!(actual, expected);
+    assert_eq!(expected, Interupt::Output(LOOPS * (LOOPS + 1) / 2));
+    println!("interpreter: {:.2?}", interpreted);
+    println!("transpiled:  {:.2?}", transpiled);
+    println!(
+        "speedup:     {:.1}x",
+        interpreted.as_secs_f64() / transpiled.as_secs_f64()
+    );
+}
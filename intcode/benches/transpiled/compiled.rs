@@ -0,0 +1,151 @@
+// Generated by intcode::transpile, do not edit
+//
+// The compiled code does not count executed instructions or enforce instruction budgets,
+// does not check watchpoints and does not update memory_flags, the journal, profile, taint,
+// trace or session of the wrapped Program. All of them work again once the interpreter takes
+// over after a fallback.
+#![allow(clippy::all, unused)]
+
+use intcode::{Interupt, Program};
+
+const MEMORY: [i64; 18] = [3, 16, 1, 17, 16, 17, 1001, 16, -1, 16, 1005, 16, 2, 4, 17, 99, 0, 0];
+
+// Cells that belong to compiled instructions
+const CODE: [bool; 18] = [true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, false, false];
+
+pub struct Compiled {
+    pub program: Program,
+    interpreted: bool,
+}
+
+impl Default for Compiled {
+    fn default() -> Self {
+        let mut program = Program::default();
+        program.reset(MEMORY.to_vec());
+        Self {
+            program,
+            interpreted: false,
+        }
+    }
+}
+
+impl Compiled {
+    pub fn run(&mut self, input: &mut Vec<i64>) -> Interupt {
+        if !self.interpreted {
+            // Like Program::run, read input is removed once when the compiled code returns
+            let mut consumed = 0;
+            let interupt = self.run_compiled(input, &mut consumed);
+            input.drain(..consumed);
+            if let Some(interupt) = interupt {
+                return interupt;
+            }
+        }
+        self.program.run(input)
+    }
+    // Returns None when the interpreter has to take over
+    fn run_compiled(&mut self, input: &[i64], consumed: &mut usize) -> Option<Interupt> {
+        let mut ip = self.program.instruction_pointer;
+        loop {
+            match ip {
+                0 => {
+                    // 0000: IN -> [16]
+                    {
+                        let Some(value) = input.get(*consumed).copied() else {
+                            self.program.instruction_pointer = 0;
+                            return Some(Interupt::Input);
+                        };
+                        let dest = 16i64;
+                        if dest < 0 || is_code(dest) || !is_writable(&self.program, dest) { return self.fallback(0); }
+                        write(&mut self.program, dest, value);
+                        *consumed += 1;
+                    }
+                    ip = 2;
+                    continue;
+                }
+                2 => {
+                    // 0002: ADD [17], [16] -> [17]
+                    {
+                        let a = read(&self.program, 17);
+                        let b = read(&self.program, 16);
+                        let dest = 17i64;
+                        if dest < 0 || is_code(dest) || !is_writable(&self.program, dest) { return self.fallback(2); }
+                        let Some(value) = a.checked_add(b) else { return self.fallback(2); };
+                        write(&mut self.program, dest, value);
+                    }
+                    // 0006: ADD [16], #-1 -> [16]
+                    {
+                        let a = read(&self.program, 16);
+                        let b = -1i64;
+                        let dest = 16i64;
+                        if dest < 0 || is_code(dest) || !is_writable(&self.program, dest) { return self.fallback(6); }
+                        let Some(value) = a.checked_add(b) else { return self.fallback(6); };
+                        write(&mut self.program, dest, value);
+                    }
+                    // 0010: JT [16], #2
+                    {
+                        let a = read(&self.program, 16);
+                        let b = 2i64;
+                        if a != 0 {
+                            if b < 0 || !is_mapped(&self.program, b) { return self.fallback(10); }
+                            ip = b as usize;
+                        } else {
+                            ip = 13;
+                        }
+                        continue;
+                    }
+                }
+                13 => {
+                    // 0013: OUT [17]
+                    {
+                        let a = read(&self.program, 17);
+                        self.program.instruction_pointer = 15;
+                        return Some(Interupt::Output(a));
+                    }
+                }
+                15 => {
+                    // 0015: HLT
+                    {
+                        self.program.instruction_pointer = 15;
+                        return Some(Interupt::Halt);
+                    }
+                }
+                _ => return self.fallback(ip),
+            }
+        }
+    }
+    // Continue in the interpreter from ip, the compiled code is not used anymore
+    fn fallback(&mut self, ip: usize) -> Option<Interupt> {
+        self.program.instruction_pointer = ip;
+        self.program.invalidate_cache();
+        self.interpreted = true;
+        None
+    }
+}
+
+// Dense memory is accessed directly, the Program methods are not inlined across crates
+fn read(program: &Program, addr: i64) -> i64 {
+    match program.memory.get(addr as usize) {
+        Some(value) => *value,
+        None => program.peek(addr as usize),
+    }
+}
+
+// addr was checked with Program::is_writable
+fn write(program: &mut Program, addr: i64, value: i64) {
+    match program.memory.get_mut(addr as usize) {
+        Some(cell) => *cell = value,
+        None => program.poke(addr as usize, value).unwrap(),
+    }
+}
+
+fn is_code(addr: i64) -> bool {
+    CODE.get(addr as usize).copied().unwrap_or(false)
+}
+
+fn is_writable(program: &Program, addr: i64) -> bool {
+    (addr as usize) < program.memory.len() || program.is_writable(addr as usize)
+}
+
+fn is_mapped(program: &Program, addr: i64) -> bool {
+    (addr as usize) < program.memory.len() || program.is_mapped(addr as usize)
+}
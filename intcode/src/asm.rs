@@ -0,0 +1,359 @@
+use crate::vm::Inst;
+use std::collections::HashMap;
+use std::fmt;
+
+// Assembles intcode source into a memory image.
+//
+// The syntax matches the output of the disassembler:
+//
+//     .const LIMIT 10
+//     start:
+//         in [counter]
+//     loop:
+//         add [counter], #-1 -> [counter]
+//         out [rb+2]
+//         lt #0, [counter] -> [flag]
+//         jt [flag], #loop
+//         hlt
+//     counter: .data 0
+//     flag: .data 0, LIMIT
+//
+// Operands are `#value` (immediate), `[value]` (position) or `[rb+value]` / `[rb-value]`
+// (relative), where a value is a number, or a label or constant that is optionally negated
+// with `-` and followed by `+N` or `-N`.
+// The output operand can be separated with either `,` or `->`.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut items = Vec::new();
+    let mut symbols: HashMap<String, i64> = HashMap::new();
+    let mut addr = 0;
+
+    for (idx, raw_line) in source.lines().enumerate() {
+        let line_nr = idx + 1;
+        let err = |kind| AsmError {
+            line: line_nr,
+            kind,
+        };
+
+        let mut line = raw_line.split(';').next().unwrap().trim();
+
+        // Labels, multiple labels can be placed in front of a single statement
+        while let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_symbol(label) {
+                break;
+            }
+            if symbols.insert(label.to_string(), addr).is_some() {
+                return Err(err(AsmErrorKind::DuplicateSymbol(label.to_string())));
+            }
+            line = rest.trim();
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let (word, rest) = match line.split_once(char::is_whitespace) {
+            Some((word, rest)) => (word, rest.trim()),
+            None => (line, ""),
+        };
+
+        match word.to_lowercase().as_str() {
+            ".const" => {
+                let (name, value) = match rest.split_once(char::is_whitespace) {
+                    Some((name, value)) if is_symbol(name) => (name, value.trim()),
+                    _ => return Err(err(AsmErrorKind::BadDirective(line.to_string()))),
+                };
+                let value = match parse_value(value) {
+                    Some(Value::Number(value)) => value,
+                    _ => return Err(err(AsmErrorKind::BadOperand(value.to_string()))),
+                };
+                if symbols.insert(name.to_string(), value).is_some() {
+                    return Err(err(AsmErrorKind::DuplicateSymbol(name.to_string())));
+                }
+            }
+            ".data" => {
+                let mut values = Vec::new();
+                for value in rest.split(',') {
+                    let value = value.trim();
+                    values.push(
+                        parse_value(value)
+                            .ok_or_else(|| err(AsmErrorKind::BadOperand(value.to_string())))?,
+                    );
+                }
+                addr += values.len() as i64;
+                items.push((line_nr, Item::Data(values)));
+            }
+            mnemonic => {
+                let inst = Inst::ALL
+                    .into_iter()
+                    .find(|inst| inst.mnemonic() == mnemonic)
+                    .ok_or_else(|| err(AsmErrorKind::UnknownMnemonic(word.to_string())))?;
+
+                // Instructions with only an output operand are written as `in -> [addr]`
+                let rest = rest.strip_prefix("->").unwrap_or(rest).trim();
+
+                let mut operands = Vec::new();
+                if !rest.is_empty() {
+                    for operand in rest.split("->").flat_map(|part| part.split(',')) {
+                        let operand = operand.trim();
+                        operands.push(
+                            parse_operand(operand).ok_or_else(|| {
+                                err(AsmErrorKind::BadOperand(operand.to_string()))
+                            })?,
+                        );
+                    }
+                }
+
+                if operands.len() != inst.params() {
+                    return Err(err(AsmErrorKind::OperandCount {
+                        expected: inst.params(),
+                        found: operands.len(),
+                    }));
+                }
+                if let Some(idx) = inst.output_param() {
+                    if operands[idx].0 == 1 {
+                        return Err(err(AsmErrorKind::ImmediateOutput));
+                    }
+                }
+
+                addr += inst.params() as i64 + 1;
+                items.push((line_nr, Item::Inst(inst, operands)));
+            }
+        }
+    }
+
+    let mut memory = Vec::with_capacity(addr as usize);
+    for (line, item) in items {
+        let resolve = |value: &Value| {
+            value
+                .resolve(&symbols)
+                .map_err(|kind| AsmError { line, kind })
+        };
+
+        match item {
+            Item::Inst(inst, operands) => {
+                let modes = [100, 1000, 10000];
+                let mut opcode = inst.code();
+                for (operand, multiplier) in operands.iter().zip(modes) {
+                    opcode += operand.0 as i64 * multiplier;
+                }
+                memory.push(opcode);
+                for (_, value) in operands.iter() {
+                    memory.push(resolve(value)?);
+                }
+            }
+            Item::Data(values) => {
+                for value in values.iter() {
+                    memory.push(resolve(value)?);
+                }
+            }
+        }
+    }
+
+    Ok(memory)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnknownMnemonic(String),
+    BadDirective(String),
+    BadOperand(String),
+    OperandCount { expected: usize, found: usize },
+    ImmediateOutput,
+    UndefinedSymbol(String),
+    DuplicateSymbol(String),
+    // A negated symbol or a symbol plus offset does not fit in an i64
+    Overflow(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AsmErrorKind::UnknownMnemonic(word) => write!(f, "unknown mnemonic {}", word),
+            AsmErrorKind::BadDirective(line) => write!(f, "malformed directive {}", line),
+            AsmErrorKind::BadOperand(operand) => write!(f, "malformed operand {}", operand),
+            AsmErrorKind::OperandCount { expected, found } => {
+                write!(f, "expected {} operands, found {}", expected, found)
+            }
+            AsmErrorKind::ImmediateOutput => write!(f, "output operand in immediate mode"),
+            AsmErrorKind::UndefinedSymbol(name) => write!(f, "undefined symbol {}", name),
+            AsmErrorKind::DuplicateSymbol(name) => write!(f, "symbol {} defined twice", name),
+            AsmErrorKind::Overflow(name) => write!(f, "value of {} overflows", name),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+enum Item {
+    // Instruction with (parameter mode, value) operands
+    Inst(Inst, Vec<(u8, Value)>),
+    Data(Vec<Value>),
+}
+
+enum Value {
+    Number(i64),
+    // The value of name, negated if negative, plus offset
+    Symbol {
+        name: String,
+        negative: bool,
+        offset: i64,
+    },
+}
+
+impl Value {
+    fn resolve(&self, symbols: &HashMap<String, i64>) -> Result<i64, AsmErrorKind> {
+        match self {
+            Value::Number(value) => Ok(*value),
+            Value::Symbol {
+                name,
+                negative,
+                offset,
+            } => {
+                let value = *symbols
+                    .get(name)
+                    .ok_or_else(|| AsmErrorKind::UndefinedSymbol(name.clone()))?;
+                let value = if *negative {
+                    value.checked_neg()
+                } else {
+                    Some(value)
+                };
+                value
+                    .and_then(|value| value.checked_add(*offset))
+                    .ok_or_else(|| AsmErrorKind::Overflow(name.clone()))
+            }
+        }
+    }
+}
+
+fn is_symbol(word: &str) -> bool {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_value(value: &str) -> Option<Value> {
+    let value = value.trim();
+    if let Ok(number) = value.parse() {
+        return Some(Value::Number(number));
+    }
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value.trim()),
+        None => (false, value),
+    };
+
+    let (name, offset) = match value.find(['+', '-']) {
+        Some(idx) => {
+            let offset: i64 = value[idx + 1..].trim().parse().ok()?;
+            let offset = if value[idx..].starts_with('-') {
+                -offset
+            } else {
+                offset
+            };
+            (value[..idx].trim(), offset)
+        }
+        None => (value, 0),
+    };
+
+    if !is_symbol(name) {
+        return None;
+    }
+    Some(Value::Symbol {
+        name: name.to_string(),
+        negative,
+        offset,
+    })
+}
+
+fn parse_operand(operand: &str) -> Option<(u8, Value)> {
+    if let Some(value) = operand.strip_prefix('#') {
+        return Some((1, parse_value(value)?));
+    }
+
+    let inner = operand.strip_prefix('[')?.strip_suffix(']')?.trim();
+    if let Some(offset) = inner.strip_prefix("rb") {
+        let offset = offset.trim();
+        if offset.is_empty() {
+            return Some((2, Value::Number(0)));
+        }
+        if let Some(value) = offset.strip_prefix('+') {
+            return Some((2, parse_value(value)?));
+        }
+        if offset.starts_with('-') {
+            return Some((2, parse_value(offset)?));
+        }
+    }
+
+    Some((0, parse_value(inner)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::{self, Cell};
+
+    const EXAMPLE: &str = "
+        .const LIMIT 10
+        start:
+            in [counter]
+        loop:
+            add [counter], #-1 -> [counter]
+            out [rb+2]
+            lt #0, [counter] -> [flag]
+            jt [flag], #loop
+            hlt
+        counter: .data 0
+        flag: .data 0, LIMIT";
+
+    #[test]
+    fn round_trip() {
+        let memory = assemble(EXAMPLE).unwrap();
+        let source = disasm::disassemble(&memory, &[])
+            .into_iter()
+            .map(|line| match line.cell {
+                Cell::Inst(inst) | Cell::Guess(inst) => inst.to_string(),
+                Cell::Data(value) | Cell::Unknown(value) => format!(".data {}", value),
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        assert_eq!(assemble(&source).unwrap(), memory);
+    }
+
+    #[test]
+    fn negative_labels() {
+        let memory = assemble("out [rb-end]\nout [rb-end+1]\nout #-end-1\nend: hlt").unwrap();
+        assert_eq!(memory, vec![204, -6, 204, -5, 104, -7, 99]);
+        assert_eq!(
+            assemble("out [rb-1x]").unwrap_err().kind,
+            AsmErrorKind::BadOperand("[rb-1x]".to_string())
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let error = assemble(
+            ".const X -9223372036854775808
+out #-X",
+        )
+        .unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.kind, AsmErrorKind::Overflow("X".to_string()));
+
+        let error = assemble(
+            ".const X 9223372036854775807
+.data X+1",
+        )
+        .unwrap_err();
+        assert_eq!(error.kind, AsmErrorKind::Overflow("X".to_string()));
+    }
+}
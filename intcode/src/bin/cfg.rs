@@ -0,0 +1,35 @@
+use intcode::cfg::Cfg;
+use intcode::{Io, Program};
+use std::env;
+use std::fs;
+
+// Usage: cfg [--run] [puzzle.txt] [input values...]
+// Prints the control flow graph as Graphviz source, --run first runs the program with the
+// inputs so the targets of indirect jumps it executed are explored too
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    let run = args.next_if(|arg| arg == "--run").is_some();
+    let path = args.next().unwrap_or("./puzzle.txt".to_string());
+    let puzzle = fs::read_to_string(&path).unwrap();
+    let mut input: Vec<i64> = args.map(|v| v.parse().unwrap()).collect();
+    let memory = intcode::parse(&puzzle);
+
+    let cfg = if run {
+        let mut program = Program::default();
+        program.reset(memory.clone());
+        let (_, end) = intcode::run_to_end(&mut program, &mut input);
+        if end != Io::Halt {
+            eprintln!("{}", end);
+        }
+        Cfg::from_run(&memory, &program.memory_flags)
+    } else {
+        Cfg::new(&memory)
+    };
+
+    eprintln!(
+        "{} blocks, {} functions",
+        cfg.blocks.len(),
+        cfg.functions.len()
+    );
+    print!("{}", cfg.dot());
+}
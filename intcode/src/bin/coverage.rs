@@ -0,0 +1,37 @@
+use intcode::coverage::Coverage;
+use intcode::{Io, Program};
+use std::env;
+use std::fs;
+
+// Usage: coverage [puzzle.txt] [inputs...]
+// Every inputs argument is one run, with its input values separated by commas
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args.next().unwrap_or("./puzzle.txt".to_string());
+    let image = intcode::parse(&fs::read_to_string(&path).unwrap());
+
+    let mut runs = args.collect::<Vec<String>>();
+    if runs.is_empty() {
+        runs.push(String::new());
+    }
+
+    let mut coverage = Coverage::new(&image);
+    let mut program = Program::default();
+    program.enable_profile();
+    for run in runs {
+        let mut input: Vec<i64> = run
+            .split(',')
+            .filter(|v| !v.is_empty())
+            .map(|v| v.parse().unwrap())
+            .collect();
+
+        program.reset(image.clone());
+        let (_, end) = intcode::run_to_end(&mut program, &mut input);
+        if end != Io::Halt {
+            eprintln!("run {:?}: {}", run, end);
+        }
+        coverage.add(&program);
+    }
+
+    print!("{}", coverage);
+}
@@ -0,0 +1,20 @@
+use intcode::debugger::Debugger;
+use intcode::Program;
+use std::env;
+use std::fs;
+use std::io::{stdin, stdout};
+
+// Usage: debugger [puzzle.txt] [input values...]
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args.next().unwrap_or("./puzzle.txt".to_string());
+    let puzzle = fs::read_to_string(&path).unwrap();
+
+    let mut program = Program::default();
+    program.reset(intcode::parse(&puzzle));
+
+    let mut debugger = Debugger::new(program);
+    debugger.input = args.map(|v| v.parse().unwrap()).collect();
+
+    debugger.repl(stdin().lock(), stdout()).unwrap();
+}
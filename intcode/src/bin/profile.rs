@@ -0,0 +1,31 @@
+use intcode::{Io, Program};
+use std::env;
+use std::fs;
+
+// Usage: profile [--csv] [puzzle.txt] [input values...]
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    let csv = args.next_if(|arg| arg == "--csv").is_some();
+    let path = args.next().unwrap_or("./puzzle.txt".to_string());
+    let puzzle = fs::read_to_string(&path).unwrap();
+    let mut input: Vec<i64> = args.map(|v| v.parse().unwrap()).collect();
+
+    let mut program = Program::default();
+    program.reset(intcode::parse(&puzzle));
+    program.enable_profile();
+
+    let (outputs, end) = intcode::run_to_end(&mut program, &mut input);
+    for value in outputs {
+        eprintln!("output: {}", value);
+    }
+    if end != Io::Halt {
+        eprintln!("{}", end);
+    }
+
+    let profile = program.profile().unwrap();
+    if csv {
+        print!("{}", profile.csv(&program));
+    } else {
+        print!("{}", profile.report(&program, 20));
+    }
+}
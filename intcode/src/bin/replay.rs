@@ -0,0 +1,38 @@
+use intcode::session::Session;
+use intcode::Interupt;
+use std::env;
+use std::process;
+
+// Usage: replay session.txt
+// Replays a recorded input session and prints the outputs and how the program stopped
+fn main() {
+    let path = env::args().nth(1).unwrap_or("./session.txt".to_string());
+    let session = match Session::load(&path) {
+        Ok(session) => session,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            process::exit(2);
+        }
+    };
+
+    match session.replay() {
+        Ok(replay) => {
+            let outputs = replay
+                .outputs
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<String>>();
+            println!("outputs: {}", outputs.join(","));
+            match replay.end {
+                Interupt::Halt => println!("halted"),
+                Interupt::Input => println!("waiting for input after the last recorded value"),
+                Interupt::Fault(fault) => println!("fault: {}", fault),
+                end => println!("stopped: {:?}", end),
+            }
+        }
+        Err(err) => {
+            eprintln!("replay diverged: {}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use intcode::selfmod::CodePolicy;
+use intcode::{Io, Program};
+use std::env;
+use std::fs;
+
+// Usage: selfmod [--fault] [puzzle.txt] [input values...]
+// Reports every instruction that writes over executed code, --fault stops at the first one
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    let policy = match args.next_if(|arg| arg == "--fault") {
+        Some(_) => CodePolicy::Fault,
+        None => CodePolicy::Warn,
+    };
+    let path = args.next().unwrap_or("./puzzle.txt".to_string());
+    let puzzle = fs::read_to_string(&path).unwrap();
+    let mut input: Vec<i64> = args.map(|v| v.parse().unwrap()).collect();
+
+    let mut program = Program::default();
+    program.set_code_policy(policy);
+    program.reset(intcode::parse(&puzzle));
+
+    let (_, end) = intcode::run_to_end(&mut program, &mut input);
+    if end != Io::Halt {
+        eprintln!("{}", end);
+    }
+
+    print!("{}", program.self_modify_report());
+}
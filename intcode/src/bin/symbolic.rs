@@ -0,0 +1,67 @@
+use intcode::symbolic::{Symbolic, Target};
+use std::env;
+use std::fs;
+
+// Usage: symbolic [--memory addr,...] [--inputs count] [--domain lo,hi] [puzzle.txt] value
+// Prints every assignment of the symbols for which the result equals value. The result is the
+// last output, or memory 0 for programs that only take symbolic memory like day 2.
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    let mut addrs = Vec::new();
+    let mut inputs = 0;
+    let mut domain = 0..=99;
+    loop {
+        if args.next_if(|arg| arg == "--memory").is_some() {
+            let values = args.next().unwrap();
+            addrs = values.split(',').map(|v| v.parse().unwrap()).collect();
+        } else if args.next_if(|arg| arg == "--inputs").is_some() {
+            inputs = args.next().unwrap().parse().unwrap();
+        } else if args.next_if(|arg| arg == "--domain").is_some() {
+            let values = args.next().unwrap();
+            let (lo, hi) = values.split_once(',').unwrap();
+            domain = lo.parse().unwrap()..=hi.parse().unwrap();
+        } else {
+            break;
+        }
+    }
+    let mut rest: Vec<String> = args.collect();
+    let value: i64 = rest.pop().expect("missing value").parse().unwrap();
+    let path = rest.pop().unwrap_or("./puzzle.txt".to_string());
+    let puzzle = fs::read_to_string(&path).unwrap();
+
+    let mut symbolic = Symbolic::new(&intcode::parse(&puzzle));
+    for addr in addrs {
+        symbolic.symbol_memory(addr, &format!("memory {}", addr), domain.clone());
+    }
+    for idx in 0..inputs {
+        symbolic.symbol_input(&format!("input {}", idx), domain.clone());
+    }
+    let target = if inputs > 0 {
+        Target::LastOutput
+    } else {
+        Target::Memory(0)
+    };
+
+    let solutions = match symbolic.solve(target, value) {
+        Ok(solutions) => solutions,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+    if solutions.is_empty() {
+        println!("no solutions");
+    }
+    for solution in solutions {
+        let values = symbolic
+            .symbols
+            .iter()
+            .zip(solution.0)
+            .map(|(symbol, value)| match value {
+                Some(value) => format!("{}: {}", symbol.name, value),
+                None => format!("{}: any", symbol.name),
+            })
+            .collect::<Vec<String>>();
+        println!("{}", values.join(", "));
+    }
+}
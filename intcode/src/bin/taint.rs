@@ -0,0 +1,43 @@
+use intcode::{Io, Program};
+use std::env;
+use std::fs;
+
+// Usage: taint [--memory addr,...] [puzzle.txt] [input values...]
+// Memory cells passed with --memory are treated as sources too, like day 2's noun and verb
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    let mut sources = Vec::new();
+    if args.next_if(|arg| arg == "--memory").is_some() {
+        let addrs = args.next().unwrap();
+        sources = addrs.split(',').map(|v| v.parse().unwrap()).collect();
+    }
+    let path = args.next().unwrap_or("./puzzle.txt".to_string());
+    let puzzle = fs::read_to_string(&path).unwrap();
+    let mut input: Vec<i64> = args.map(|v| v.parse().unwrap()).collect();
+
+    let mut program = Program::default();
+    program.enable_taint();
+    program.reset(intcode::parse(&puzzle));
+    for addr in sources {
+        program.taint_memory(addr);
+    }
+
+    let (_, end) = intcode::run_to_end(&mut program, &mut input);
+    if end != Io::Halt {
+        eprintln!("{}", end);
+    }
+
+    let taint = program.taint().unwrap();
+    print!("{}", taint);
+    // Programs without output leave their result in memory 0
+    let result = taint
+        .tags(0)
+        .iter()
+        .map(|source| source.to_string())
+        .collect::<Vec<String>>();
+    if result.is_empty() {
+        println!("memory 0: -");
+    } else {
+        println!("memory 0: {}", result.join(", "));
+    }
+}
@@ -0,0 +1,26 @@
+use intcode::{Io, Program};
+use std::env;
+use std::fs;
+use std::io;
+
+// Usage: trace [puzzle.txt] [input values...] > trace.jsonl
+// Writes one JSON line per executed instruction to stdout
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args.next().unwrap_or("./puzzle.txt".to_string());
+    let puzzle = fs::read_to_string(&path).unwrap();
+    let mut input: Vec<i64> = args.map(|v| v.parse().unwrap()).collect();
+
+    let mut program = Program::default();
+    program.enable_trace(io::BufWriter::new(io::stdout()));
+    program.reset(intcode::parse(&puzzle));
+
+    let (_, end) = intcode::run_to_end(&mut program, &mut input);
+    if end != Io::Halt {
+        eprintln!("{}", end);
+    }
+
+    if let Err(err) = program.finish_trace() {
+        eprintln!("writing the trace: {}", err);
+    }
+}
@@ -0,0 +1,28 @@
+use intcode::trace;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::process;
+
+// Usage: tracediff left.jsonl right.jsonl
+// Prints the first step where two traces written by the trace bin differ
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() != 2 {
+        eprintln!("usage: tracediff left.jsonl right.jsonl");
+        process::exit(2);
+    }
+    let open = |path: &str| BufReader::new(File::open(path).unwrap());
+
+    match trace::diff(open(&args[0]), open(&args[1])) {
+        Ok(None) => println!("traces are equal"),
+        Ok(Some(divergence)) => {
+            println!("{}", divergence);
+            process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    }
+}
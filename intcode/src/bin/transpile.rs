@@ -0,0 +1,15 @@
+use std::env;
+use std::fs;
+
+// Usage: transpile [puzzle.txt] [StructName] > program.rs
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args.next().unwrap_or("./puzzle.txt".to_string());
+    let name = args.next().unwrap_or("Compiled".to_string());
+    let puzzle = fs::read_to_string(&path).unwrap();
+
+    print!(
+        "{}",
+        intcode::transpile::transpile(&intcode::parse(&puzzle), &name)
+    );
+}
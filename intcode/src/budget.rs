@@ -0,0 +1,87 @@
+use crate::vm::Program;
+
+// Instruction budgets, a program that runs out returns Interupt::BudgetExhausted before
+// executing the next instruction. Nothing is lost, raising or clearing the budget and calling
+// run again continues where it stopped.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Budget {
+    // Instructions per call to Program::run
+    pub(crate) run: Option<u64>,
+    // Instructions since the last reset
+    pub(crate) total: Option<u64>,
+}
+
+impl Program {
+    pub fn set_run_budget(&mut self, instructions: u64) {
+        self.budget.run = Some(instructions);
+    }
+    pub fn set_total_budget(&mut self, instructions: u64) {
+        self.budget.total = Some(instructions);
+    }
+    pub fn clear_budgets(&mut self) {
+        self.budget = Budget::default();
+    }
+    // Instructions executed since the last reset
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+    // Instructions left of the total budget, None without one
+    pub fn remaining(&self) -> Option<u64> {
+        self.budget
+            .total
+            .map(|total| total.saturating_sub(self.executed))
+    }
+    pub(crate) fn total_budget_exhausted(&self) -> bool {
+        self.remaining() == Some(0)
+    }
+    // run_start is the executed count when Program::run was called
+    pub(crate) fn run_budget_exhausted(&self, run_start: u64) -> bool {
+        self.budget
+            .run
+            .is_some_and(|run| self.executed - run_start >= run)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::{Interupt, Program};
+
+    // Counts cell 20 down from 3, outputs 7 and halts after 9 instructions
+    const IMAGE: [i64; 14] = [1101, 3, 0, 20, 1001, 20, -1, 20, 1005, 20, 4, 104, 7, 99];
+
+    fn program() -> Program {
+        let mut program = Program::default();
+        program.reset(IMAGE.to_vec());
+        program
+    }
+
+    #[test]
+    fn run_budget_resumes() {
+        let mut program = program();
+        program.set_run_budget(4);
+        assert_eq!(program.run(&mut Vec::new()), Interupt::BudgetExhausted);
+        assert_eq!(program.executed(), 4);
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Output(7));
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Halt);
+        assert_eq!(program.executed(), 9);
+        assert_eq!(program.remaining(), None);
+    }
+
+    #[test]
+    fn total_budget_resumes() {
+        let mut program = program();
+        program.set_total_budget(5);
+        for _ in 0..2 {
+            assert_eq!(program.run(&mut Vec::new()), Interupt::BudgetExhausted);
+            assert_eq!((program.executed(), program.remaining()), (5, Some(0)));
+        }
+
+        program.set_total_budget(100);
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Output(7));
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Halt);
+        assert_eq!((program.executed(), program.remaining()), (9, Some(91)));
+
+        program.reset(IMAGE.to_vec());
+        assert_eq!((program.executed(), program.remaining()), (0, Some(100)));
+    }
+}
@@ -0,0 +1,458 @@
+use crate::disasm::{Instruction, Operand};
+use crate::vm::{Flag, Inst};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+// Control flow graph of a memory image.
+//
+// Like disasm::discover, code is found by following fall-through and immediate jump targets
+// from address 0. JumpIfTrue and JumpIfFalse with an immediate condition are resolved to an
+// unconditional jump or no jump at all. On top of that the call idiom of compiled intcode is
+// recognized, the return address is written to the stack before jumping to the function:
+//
+//     ADD #ret, #0 -> [rb+1]
+//     JT #1, #func
+//     ret: ...
+//
+// and a function returns by jumping to the address on the stack, after moving the relative base
+// back with ARB:
+//
+//     ARB #-3
+//     JT #1, [rb+0]
+//
+// The code after a call is explored as the place the call returns to. Other jumps to a computed
+// address can only be followed with the flags of a run, see Cfg::from_run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    pub blocks: BTreeMap<usize, Block>,
+    // Address 0 and every call target, in address order
+    pub functions: Vec<Function>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<(usize, Instruction)>,
+    pub exit: Exit,
+}
+
+// How control leaves a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    // Falls through into the block at the address, also for jumps that are never taken
+    Next(usize),
+    // Unconditional jump to an immediate address
+    Jump(usize),
+    // Conditional jump, target is None if it is not an immediate address
+    Branch { target: Option<usize>, next: usize },
+    // Call of the function at callee, returning at ret
+    Call { callee: usize, ret: usize },
+    // Unconditional jump to the relative base, the return of a function
+    Return,
+    // Unconditional jump to an address read from memory
+    Indirect,
+    Halt,
+    // Runs into a cell that does not decode to an instruction
+    Invalid(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Next,
+    Taken,
+    NotTaken,
+    Call,
+    // From a call to the place it returns to, the call itself summarized
+    Return,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub entry: usize,
+    // Value of the ARB the function starts with, the size of its stack frame
+    pub frame: Option<i64>,
+    // Blocks reachable from the entry without following calls
+    pub blocks: BTreeSet<usize>,
+    // Functions called by this one
+    pub calls: BTreeSet<usize>,
+}
+
+impl Block {
+    // Address after the last instruction
+    pub fn end(&self) -> usize {
+        match self.instructions.last() {
+            Some((addr, inst)) => addr + inst.size(),
+            None => self.start,
+        }
+    }
+}
+
+impl Exit {
+    pub fn successors(&self) -> Vec<(usize, Edge)> {
+        match *self {
+            Exit::Next(next) => vec![(next, Edge::Next)],
+            Exit::Jump(target) => vec![(target, Edge::Taken)],
+            Exit::Branch { target, next } => {
+                let mut successors = vec![(next, Edge::NotTaken)];
+                successors.extend(target.map(|target| (target, Edge::Taken)));
+                successors
+            }
+            Exit::Call { callee, ret } => vec![(callee, Edge::Call), (ret, Edge::Return)],
+            Exit::Return | Exit::Indirect | Exit::Halt | Exit::Invalid(_) => Vec::new(),
+        }
+    }
+}
+
+// What exploring the image found so far
+#[derive(Default)]
+struct Explorer {
+    instructions: BTreeMap<usize, Instruction>,
+    leaders: BTreeSet<usize>,
+    // Address of the jump of every call to (callee, ret)
+    calls: BTreeMap<usize, (usize, usize)>,
+}
+
+impl Explorer {
+    fn explore(&mut self, memory: &[i64], root: usize) {
+        let target = |operand: &Operand| match operand {
+            Operand::Immediate(addr) if *addr >= 0 && (*addr as usize) < memory.len() => {
+                Some(*addr as usize)
+            }
+            _ => None,
+        };
+
+        self.leaders.insert(root);
+        let mut queue = vec![root];
+        while let Some(start) = queue.pop() {
+            // Constants written to the stack since start, candidates for a return address
+            let mut pushed = Vec::new();
+            let mut addr = start;
+            while !self.instructions.contains_key(&addr) {
+                let Some(inst) = Instruction::decode(memory, addr) else {
+                    break;
+                };
+                let next = addr + inst.size();
+                self.instructions.insert(addr, inst.clone());
+
+                match inst.inst {
+                    Inst::Halt => break,
+                    Inst::JumpIfTrue | Inst::JumpIfFalse => {
+                        let jumps_on_true = inst.inst == Inst::JumpIfTrue;
+                        let taken = match inst.operands[0] {
+                            Operand::Immediate(value) => Some((value != 0) == jumps_on_true),
+                            _ => None,
+                        };
+                        let target = target(&inst.operands[1]);
+                        if taken != Some(false) {
+                            if let Some(target) = target {
+                                self.leaders.insert(target);
+                                queue.push(target);
+                            }
+                        }
+                        let call = taken == Some(true)
+                            && target.is_some()
+                            && pushed.contains(&(next as i64));
+                        if call {
+                            self.calls.insert(addr, (target.unwrap(), next));
+                        }
+                        if taken != Some(true) || call {
+                            queue.push(next);
+                        }
+                        self.leaders.insert(next);
+                        break;
+                    }
+                    Inst::Add | Inst::Multiply | Inst::LessThan | Inst::Equals => {
+                        if let [Operand::Immediate(a), Operand::Immediate(b), Operand::Relative(_)] =
+                            inst.operands[..]
+                        {
+                            pushed.push(match inst.inst {
+                                Inst::Add => a + b,
+                                Inst::Multiply => a * b,
+                                Inst::LessThan => (a < b) as i64,
+                                _ => (a == b) as i64,
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+                addr = next;
+            }
+        }
+    }
+    fn exit(&self, addr: usize, inst: &Instruction) -> Exit {
+        let next = addr + inst.size();
+        match inst.inst {
+            Inst::Halt => return Exit::Halt,
+            Inst::JumpIfTrue | Inst::JumpIfFalse => {}
+            _ if self.instructions.contains_key(&next) => return Exit::Next(next),
+            _ => return Exit::Invalid(next),
+        }
+        if let Some((callee, ret)) = self.calls.get(&addr) {
+            return Exit::Call {
+                callee: *callee,
+                ret: *ret,
+            };
+        }
+
+        let jumps_on_true = inst.inst == Inst::JumpIfTrue;
+        let taken = match inst.operands[0] {
+            Operand::Immediate(value) => Some((value != 0) == jumps_on_true),
+            _ => None,
+        };
+        let target = match inst.operands[1] {
+            Operand::Immediate(target) if self.instructions.contains_key(&(target as usize)) => {
+                Some(target as usize)
+            }
+            _ => None,
+        };
+        match (taken, target, inst.operands[1]) {
+            (Some(false), _, _) if self.instructions.contains_key(&next) => Exit::Next(next),
+            (Some(false), _, _) => Exit::Invalid(next),
+            (Some(true), Some(target), _) => Exit::Jump(target),
+            (Some(true), None, Operand::Immediate(target)) => Exit::Invalid(target as usize),
+            // Calls store the return address at [rb+0] after moving the relative base
+            (Some(true), None, Operand::Relative(0)) => Exit::Return,
+            (Some(true), None, _) => Exit::Indirect,
+            (None, target, _) => Exit::Branch { target, next },
+        }
+    }
+}
+
+impl Cfg {
+    pub fn new(memory: &[i64]) -> Self {
+        let mut explorer = Explorer::default();
+        explorer.explore(memory, 0);
+        Self::build(explorer)
+    }
+    // Also explores from every instruction a run executed that static analysis did not find,
+    // the targets of indirect jumps and returns
+    pub fn from_run(memory: &[i64], flags: &[Flag]) -> Self {
+        let mut explorer = Explorer::default();
+        explorer.explore(memory, 0);
+
+        let mut addr = 0;
+        while addr < memory.len().min(flags.len()) {
+            if let Some(inst) = explorer.instructions.get(&addr) {
+                addr += inst.size();
+            } else if flags[addr] == Flag::Inst {
+                explorer.explore(memory, addr);
+                // Code written by the program itself does not decode in the image
+                if !explorer.instructions.contains_key(&addr) {
+                    addr += 1;
+                }
+            } else {
+                addr += 1;
+            }
+        }
+
+        Self::build(explorer)
+    }
+    fn build(explorer: Explorer) -> Self {
+        let mut blocks = BTreeMap::new();
+        for start in explorer.leaders.iter() {
+            let mut instructions = Vec::new();
+            let mut addr = *start;
+            let exit = loop {
+                let Some(inst) = explorer.instructions.get(&addr) else {
+                    break Exit::Invalid(addr);
+                };
+                instructions.push((addr, inst.clone()));
+                let exit = explorer.exit(addr, inst);
+                match exit {
+                    Exit::Next(next) if !explorer.leaders.contains(&next) => addr = next,
+                    exit => break exit,
+                }
+            };
+            blocks.insert(
+                *start,
+                Block {
+                    start: *start,
+                    instructions,
+                    exit,
+                },
+            );
+        }
+        // A leader after a jump that was never explored
+        blocks.retain(|_, block| !block.instructions.is_empty());
+
+        let entries = explorer
+            .calls
+            .values()
+            .map(|(callee, _)| *callee)
+            .chain([0])
+            .collect::<BTreeSet<usize>>();
+        let functions = entries
+            .into_iter()
+            .map(|entry| Function::new(&blocks, entry))
+            .collect();
+
+        Self { blocks, functions }
+    }
+    pub fn edges(&self) -> Vec<(usize, usize, Edge)> {
+        let mut edges = Vec::new();
+        for block in self.blocks.values() {
+            for (to, edge) in block.exit.successors() {
+                if self.blocks.contains_key(&to) {
+                    edges.push((block.start, to, edge));
+                }
+            }
+        }
+        edges
+    }
+    // Graphviz source with a cluster per function, blocks shared by functions are drawn in the
+    // first one
+    pub fn dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph intcode {{").unwrap();
+        writeln!(out, "    node [shape=box, fontname=monospace];").unwrap();
+
+        let mut drawn = BTreeSet::new();
+        for function in self.functions.iter() {
+            writeln!(out, "    subgraph cluster_{} {{", function.entry).unwrap();
+            let label = match function.frame {
+                Some(frame) => format!("fn {:04} (frame {})", function.entry, frame),
+                None => format!("fn {:04}", function.entry),
+            };
+            writeln!(out, "        label=\"{}\";", label).unwrap();
+            for start in function.blocks.iter() {
+                if drawn.insert(*start) {
+                    writeln!(out, "        {}", self.node(&self.blocks[start])).unwrap();
+                }
+            }
+            writeln!(out, "    }}").unwrap();
+        }
+        for block in self.blocks.values() {
+            if drawn.insert(block.start) {
+                writeln!(out, "    {}", self.node(block)).unwrap();
+            }
+        }
+
+        for (from, to, edge) in self.edges() {
+            let style = match edge {
+                Edge::Next => "",
+                Edge::Taken => " [label=\"T\"]",
+                Edge::NotTaken => " [label=\"F\"]",
+                Edge::Call => " [style=bold, color=blue]",
+                Edge::Return => " [style=dashed]",
+            };
+            writeln!(out, "    b{} -> b{}{};", from, to, style).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+    fn node(&self, block: &Block) -> String {
+        let mut label = String::new();
+        for (addr, inst) in block.instructions.iter() {
+            write!(label, "{:04}: {}\\l", addr, inst).unwrap();
+        }
+        match block.exit {
+            Exit::Return => label.push_str("return\\l"),
+            Exit::Indirect => label.push_str("indirect jump\\l"),
+            Exit::Invalid(addr) => write!(label, "invalid code at {:04}\\l", addr).unwrap(),
+            _ => {}
+        }
+        let shape = match block.exit {
+            Exit::Halt | Exit::Return => ", peripheries=2",
+            _ => "",
+        };
+        format!("b{} [label=\"{}\"{}];", block.start, label, shape)
+    }
+}
+
+impl Function {
+    fn new(blocks: &BTreeMap<usize, Block>, entry: usize) -> Self {
+        let frame = blocks.get(&entry).and_then(|block| {
+            match block
+                .instructions
+                .first()
+                .map(|(_, inst)| (inst.inst, inst.operands[0]))
+            {
+                Some((Inst::AdjustRelativeBase, Operand::Immediate(frame))) if frame > 0 => {
+                    Some(frame)
+                }
+                _ => None,
+            }
+        });
+
+        let mut function = Self {
+            entry,
+            frame,
+            blocks: BTreeSet::new(),
+            calls: BTreeSet::new(),
+        };
+        let mut queue = vec![entry];
+        while let Some(start) = queue.pop() {
+            let Some(block) = blocks.get(&start) else {
+                continue;
+            };
+            if !function.blocks.insert(start) {
+                continue;
+            }
+            for (to, edge) in block.exit.successors() {
+                match edge {
+                    Edge::Call => _ = function.calls.insert(to),
+                    _ => queue.push(to),
+                }
+            }
+        }
+        function
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::{Io, Program};
+
+    fn run(memory: &[i64]) -> Program {
+        let mut program = Program::default();
+        program.reset(memory.to_vec());
+        let (_, end) = crate::run_to_end(&mut program, &mut Vec::new());
+        assert_eq!(end, Io::Halt);
+        program
+    }
+
+    #[test]
+    fn calls_and_returns() {
+        let memory = [
+            109, 100, 21101, 9, 0, 0, 1105, 1, 12, 104, 42, 99, 109, 2, 21101, 1, 2, -1, 1206, -1,
+            23, 104, 7, 109, -2, 2105, 1, 0,
+        ];
+        let cfg = Cfg::new(&memory);
+
+        assert_eq!(
+            cfg.blocks.keys().copied().collect::<Vec<_>>(),
+            [0, 9, 12, 21, 23]
+        );
+        assert_eq!(cfg.blocks[&0].exit, Exit::Call { callee: 12, ret: 9 });
+        assert_eq!(cfg.blocks[&23].exit, Exit::Return);
+        assert_eq!(cfg.functions.len(), 2);
+        assert_eq!(cfg.functions[1].frame, Some(2));
+        assert_eq!(cfg.functions[1].blocks, BTreeSet::from([12, 21, 23]));
+
+        // Only a jump to [rb+0] returns
+        let cfg = Cfg::new(&[109, 10, 2105, 1, 1]);
+        assert_eq!(cfg.blocks[&0].exit, Exit::Indirect);
+    }
+
+    #[test]
+    fn indirect_jump_from_run() {
+        let memory = [105, 1, 4, 99, 6, 0, 104, 5, 99];
+        assert_eq!(Cfg::new(&memory).blocks.len(), 1);
+
+        let program = run(&memory);
+        let cfg = Cfg::from_run(&memory, &program.memory_flags);
+        assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), [0, 6]);
+    }
+
+    #[test]
+    fn self_modifying_from_run() {
+        // Writes HLT to cell 9 and jumps to it
+        let memory = [1101, 100, -1, 9, 104, 42, 1105, 1, 9, 0];
+        let program = run(&memory);
+        let cfg = Cfg::from_run(&memory, &program.memory_flags);
+
+        assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), [0]);
+        assert_eq!(cfg.blocks[&0].exit, Exit::Invalid(9));
+    }
+}
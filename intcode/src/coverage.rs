@@ -0,0 +1,209 @@
+use crate::disasm::{self, Instruction, Operand};
+use crate::vm::{Flag, Inst, Program};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+// Code of a memory image executed over any number of runs.
+//
+// Code cells are the cells of instructions that were executed or are reachable according to
+// disasm::discover. Branch outcomes are only recorded for programs with profiling enabled.
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    image: Vec<i64>,
+    // Per cell of the image, true if it belongs to an executed instruction
+    executed: Vec<bool>,
+    branches: BTreeMap<usize, Branch>,
+}
+
+// Outcomes seen for a JumpIfTrue or JumpIfFalse instruction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Branch {
+    pub taken: bool,
+    pub not_taken: bool,
+}
+
+impl Coverage {
+    pub fn new(image: &[i64]) -> Self {
+        Self {
+            image: image.to_vec(),
+            executed: vec![false; image.len()],
+            branches: BTreeMap::new(),
+        }
+    }
+    // Adds what the program executed since it was reset with this image
+    pub fn add(&mut self, program: &Program) {
+        for (executed, flag) in self.executed.iter_mut().zip(program.memory_flags.iter()) {
+            if let Flag::Inst | Flag::Param = flag {
+                *executed = true;
+            }
+        }
+
+        if let Some(profile) = program.profile() {
+            for addr in profile.taken.keys() {
+                self.branches.entry(*addr).or_default().taken = true;
+            }
+            for addr in profile.not_taken.keys() {
+                self.branches.entry(*addr).or_default().not_taken = true;
+            }
+        }
+    }
+    pub fn merge(&mut self, other: &Coverage) {
+        for (executed, other) in self.executed.iter_mut().zip(other.executed.iter()) {
+            *executed |= *other;
+        }
+        for (addr, other) in other.branches.iter() {
+            let branch = self.branches.entry(*addr).or_default();
+            branch.taken |= other.taken;
+            branch.not_taken |= other.not_taken;
+        }
+    }
+    fn code_cells(&self) -> Vec<bool> {
+        let mut code = self.executed.clone();
+        for (addr, inst) in disasm::discover(&self.image).0 {
+            let end = (addr + inst.size()).min(code.len());
+            code[addr..end].fill(true);
+        }
+        code
+    }
+    // Executed and total number of code cells
+    pub fn cells(&self) -> (usize, usize) {
+        let code = self.code_cells();
+        let executed = self.executed.iter().filter(|executed| **executed).count();
+        (executed, code.iter().filter(|code| **code).count())
+    }
+    pub fn percentage(&self) -> f64 {
+        match self.cells() {
+            (_, 0) => 0.0,
+            (executed, total) => executed as f64 * 100.0 / total as f64,
+        }
+    }
+    // Ranges of code cells that were never executed
+    pub fn unexecuted(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for (addr, code) in self.code_cells().into_iter().enumerate() {
+            if !code || self.executed[addr] {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(range) if range.end == addr => range.end += 1,
+                _ => ranges.push(addr..addr + 1),
+            }
+        }
+        ranges
+    }
+    // Outcomes of every reachable or executed jump instruction
+    pub fn branches(&self) -> Vec<(usize, Branch)> {
+        let mut branches = self.branches.clone();
+        for (addr, inst) in disasm::discover(&self.image).0 {
+            if let Inst::JumpIfTrue | Inst::JumpIfFalse = inst.inst {
+                branches.entry(addr).or_default();
+            }
+        }
+        branches.into_iter().collect()
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (executed, total) = self.cells();
+        writeln!(
+            f,
+            "code: {}/{} cells executed ({:.2}%)",
+            executed,
+            total,
+            self.percentage()
+        )?;
+
+        let unexecuted = self
+            .unexecuted()
+            .iter()
+            .map(|range| format!("{}..{}", range.start, range.end))
+            .collect::<Vec<String>>();
+        if unexecuted.is_empty() {
+            writeln!(f, "unexecuted: none")?;
+        } else {
+            writeln!(f, "unexecuted: {}", unexecuted.join(", "))?;
+        }
+
+        let branches = self.branches();
+        let seen: usize = branches
+            .iter()
+            .map(|(_, branch)| branch.taken as usize + branch.not_taken as usize)
+            .sum();
+        // A jump on an immediate value always goes the same way
+        let possible: usize = branches
+            .iter()
+            .map(|(addr, _)| match Instruction::decode(&self.image, *addr) {
+                Some(inst) if matches!(inst.operands.first(), Some(Operand::Immediate(_))) => 1,
+                _ => 2,
+            })
+            .sum();
+        writeln!(f, "branches: {}/{} outcomes seen", seen, possible)?;
+        for (addr, branch) in branches {
+            let outcome = match (branch.taken, branch.not_taken) {
+                (true, true) => "taken, not taken",
+                (true, false) => "taken",
+                (false, true) => "not taken",
+                (false, false) => "not executed",
+            };
+            let inst = Instruction::decode(&self.image, addr)
+                .map(|inst| inst.to_string())
+                .unwrap_or_default();
+            writeln!(f, "  {:04}: {:<24} {}", addr, inst, outcome)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::Interupt;
+
+    // Outputs 1 if the input is 0, otherwise 2
+    const IMAGE: [i64; 13] = [3, 12, 1005, 12, 8, 104, 1, 99, 104, 2, 99, 0, 0];
+
+    fn run(input: i64) -> Coverage {
+        let mut program = Program::default();
+        program.enable_profile();
+        program.reset(IMAGE.to_vec());
+        let mut input = vec![input];
+        assert!(matches!(program.run(&mut input), Interupt::Output(_)));
+        assert_eq!(program.run(&mut input), Interupt::Halt);
+
+        let mut coverage = Coverage::new(&IMAGE);
+        coverage.add(&program);
+        coverage
+    }
+
+    #[test]
+    fn merged_runs() {
+        let mut coverage = run(0);
+        assert_eq!(coverage.cells(), (8, 11));
+        assert!(coverage.to_string().contains("\nunexecuted: 8..11\n"));
+        let not_taken = Branch {
+            taken: false,
+            not_taken: true,
+        };
+        assert_eq!(coverage.branches(), [(2, not_taken)]);
+
+        coverage.merge(&run(5));
+        assert_eq!(coverage.cells(), (11, 11));
+        assert!(coverage.unexecuted().is_empty());
+        assert_eq!(
+            coverage.branches(),
+            [(
+                2,
+                Branch {
+                    taken: true,
+                    not_taken: true
+                }
+            )]
+        );
+        assert!(coverage
+            .to_string()
+            .contains("code: 11/11 cells executed (100.00%)\nunexecuted: none\nbranches: 2/2"));
+    }
+}
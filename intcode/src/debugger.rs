@@ -0,0 +1,324 @@
+use crate::disasm;
+use crate::error::VmError;
+use crate::vm::{Access, Interupt, Program};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+// Instructions kept in the journal, so long continues do not grow it without bound
+const JOURNAL_LIMIT: usize = 100_000;
+
+const HELP: &str = "\
+s, step [n]            execute n instructions (default 1)
+bs, back [n]           undo n instructions (default 1), up to the last 100000
+lw <addr>              undo until the last write to addr
+c, continue            run until a breakpoint, watchpoint, input request, halt or fault
+b, break [addr]        set a breakpoint, without addr list all breakpoints
+d, delete <addr>       remove a breakpoint
+w, watch <addr> [len]  stop when memory in the range is read or written
+uw, unwatch <addr>     remove the watchpoints covering addr
+r, regs                print the registers and the pending input
+x <addr> [len]         print memory
+set <addr> <v>...      patch memory starting at addr
+in <v>...              append values to the input queue
+l, list [addr] [n]     disassemble n lines (default 10) starting at addr
+q, quit                exit the debugger";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    // The requested number of steps was executed
+    Stepped,
+    Breakpoint(usize),
+    Interupt(Interupt),
+}
+
+pub struct Debugger {
+    pub program: Program,
+    pub input: Vec<i64>,
+    pub output: Vec<i64>,
+    pub breakpoints: BTreeSet<usize>,
+}
+
+impl Debugger {
+    pub fn new(mut program: Program) -> Self {
+        program.enable_journal();
+        program.set_journal_limit(JOURNAL_LIMIT);
+
+        Self {
+            program,
+            input: Vec::new(),
+            output: Vec::new(),
+            breakpoints: BTreeSet::new(),
+        }
+    }
+    pub fn step(&mut self) -> Stop {
+        match self.program.step(&mut self.input) {
+            Some(Interupt::Output(value)) => {
+                self.output.push(value);
+                Stop::Interupt(Interupt::Output(value))
+            }
+            Some(interupt) => Stop::Interupt(interupt),
+            None => Stop::Stepped,
+        }
+    }
+    // Runs until a breakpoint is hit, outputs are collected and do not stop execution
+    pub fn cont(&mut self) -> Stop {
+        loop {
+            match self.step() {
+                Stop::Stepped | Stop::Interupt(Interupt::Output(_)) => {}
+                stop => return stop,
+            }
+
+            let ip = self.program.instruction_pointer;
+            if self.breakpoints.contains(&ip) {
+                return Stop::Breakpoint(ip);
+            }
+        }
+    }
+    pub fn registers(&self) -> String {
+        format!(
+            "ip: {} rb: {} input: {:?}",
+            self.program.instruction_pointer, self.program.relative_base, self.input
+        )
+    }
+    pub fn memory(&self, addr: usize, len: usize) -> Vec<i64> {
+        // Never more cells than are allocated, unallocated memory reads as 0
+        let len = len.min(self.program.allocated());
+        (addr..addr.saturating_add(len))
+            .map(|addr| self.program.peek(addr))
+            .collect()
+    }
+    pub fn patch(&mut self, addr: usize, values: &[i64]) -> Result<(), VmError> {
+        let result = values
+            .iter()
+            .enumerate()
+            .try_for_each(|(idx, value)| self.program.poke(addr + idx, *value));
+        self.program.invalidate_cache();
+        result
+    }
+    // Executes a single debugger command and returns the text to show to the user
+    pub fn command(&mut self, line: &str) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let args = words
+            .map(|word| {
+                word.parse::<i64>()
+                    .map_err(|_| format!("not a number: {}", word))
+            })
+            .collect::<Result<Vec<i64>, String>>()?;
+        let outputs_before = self.output.len();
+        let addr = |idx: usize| -> Result<usize, String> {
+            let value = *args.get(idx).ok_or("missing address")?;
+            usize::try_from(value).map_err(|_| format!("invalid address: {}", value))
+        };
+
+        let stop = match command {
+            "s" | "step" => {
+                let mut stop = Stop::Stepped;
+                for _ in 0..args.first().copied().unwrap_or(1) {
+                    stop = self.step();
+                    if stop != Stop::Stepped {
+                        break;
+                    }
+                    let ip = self.program.instruction_pointer;
+                    if self.breakpoints.contains(&ip) {
+                        stop = Stop::Breakpoint(ip);
+                        break;
+                    }
+                }
+                stop
+            }
+            "bs" | "back" => {
+                for _ in 0..args.first().copied().unwrap_or(1) {
+                    if !self.program.step_back(&mut self.input) {
+                        return Err("nothing to undo".to_string());
+                    }
+                }
+                Stop::Stepped
+            }
+            "lw" => {
+                if !self.program.back_to_write(addr(0)?, &mut self.input) {
+                    return Err(format!("no recorded write to {}", addr(0)?));
+                }
+                Stop::Stepped
+            }
+            "c" | "continue" => self.cont(),
+            "b" | "break" if args.is_empty() => {
+                return Ok(format!("breakpoints: {:?}", self.breakpoints));
+            }
+            "b" | "break" => {
+                self.breakpoints.insert(addr(0)?);
+                return Ok(format!("breakpoints: {:?}", self.breakpoints));
+            }
+            "d" | "delete" => {
+                self.breakpoints.remove(&addr(0)?);
+                return Ok(format!("breakpoints: {:?}", self.breakpoints));
+            }
+            "w" | "watch" => {
+                let start = addr(0)?;
+                let len = args.get(1).copied().unwrap_or(1).max(1) as usize;
+                self.program
+                    .watch(start..start.saturating_add(len), Access::ReadWrite);
+                return Ok(format!("watchpoints: {:?}", self.program.watchpoints));
+            }
+            "uw" | "unwatch" => {
+                self.program.unwatch(addr(0)?);
+                return Ok(format!("watchpoints: {:?}", self.program.watchpoints));
+            }
+            "r" | "regs" => return Ok(self.registers()),
+            "x" => {
+                let start = addr(0)?;
+                let len = args.get(1).copied().unwrap_or(8).max(0) as usize;
+                return Ok(format!("{:04}: {:?}", start, self.memory(start, len)));
+            }
+            "set" => {
+                let start = addr(0)?;
+                self.patch(start, &args[1..])
+                    .map_err(|error| error.to_string())?;
+                return Ok(format!(
+                    "{:04}: {:?}",
+                    start,
+                    self.memory(start, args.len() - 1)
+                ));
+            }
+            "in" => {
+                self.input.extend_from_slice(&args);
+                return Ok(format!("input: {:?}", self.input));
+            }
+            "l" | "list" => {
+                let start = match args.first() {
+                    Some(_) => addr(0)?,
+                    None => self.program.instruction_pointer,
+                };
+                let len = args.get(1).copied().unwrap_or(10).max(0) as usize;
+                return Ok(self.list(start, len));
+            }
+            "h" | "help" => return Ok(HELP.to_string()),
+            "" => return Ok(String::new()),
+            _ => return Err(format!("unknown command: {}, try help", command)),
+        };
+
+        let mut status = match stop {
+            Stop::Stepped | Stop::Interupt(Interupt::Output(_)) => String::new(),
+            Stop::Breakpoint(ip) => format!("breakpoint {}\n", ip),
+            Stop::Interupt(Interupt::Input) => "waiting for input\n".to_string(),
+            Stop::Interupt(Interupt::Halt) => "halted\n".to_string(),
+            Stop::Interupt(Interupt::Fault(fault)) => format!("fault: {}\n", fault),
+            Stop::Interupt(Interupt::Watch { addr, old, new, ip }) => {
+                format!("watch {}: {} -> {} by {}\n", addr, old, new, ip)
+            }
+            Stop::Interupt(Interupt::BudgetExhausted) => {
+                "instruction budget exhausted\n".to_string()
+            }
+        };
+        if self.output.len() > outputs_before {
+            status = format!("output: {:?}\n{}", &self.output[outputs_before..], status);
+        }
+        Ok(format!(
+            "{}{}\n{}",
+            status,
+            self.registers(),
+            self.list(self.program.instruction_pointer, 1)
+        ))
+    }
+    fn list(&self, addr: usize, len: usize) -> String {
+        let program = &self.program;
+        disasm::disassemble_from(&program.memory, &program.memory_flags, addr, len)
+            .into_iter()
+            .map(|line| {
+                let marker = if line.addr == program.instruction_pointer {
+                    '>'
+                } else if self.breakpoints.contains(&line.addr) {
+                    '*'
+                } else {
+                    ' '
+                };
+                format!("{}{}", marker, line)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+    // Reads commands from input until quit or end of input
+    pub fn repl(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        write!(output, "(intcode) ")?;
+        output.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            let command = line.trim();
+            if command == "q" || command == "quit" {
+                break;
+            }
+
+            match self.command(command) {
+                Ok(text) if text.is_empty() => {}
+                Ok(text) => writeln!(output, "{}", text)?,
+                Err(err) => writeln!(output, "error: {}", err)?,
+            }
+            write!(output, "(intcode) ")?;
+            output.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn huge_ranges() {
+        let mut program = Program::default();
+        program.reset(vec![1101, 1, 2, 5, 99]);
+        let mut debugger = Debugger::new(program);
+
+        let max = i64::MAX.to_string();
+        assert!(debugger.command(&format!("w 3 {}", max)).is_ok());
+        assert_eq!(
+            debugger.program.watchpoints[0].addrs,
+            3..3 + i64::MAX as usize
+        );
+        assert!(debugger.command(&format!("w {} {}", max, max)).is_ok());
+        let dump = debugger.command(&format!("x 0 {}", max)).unwrap();
+        assert_eq!(dump, "0000: [1101, 1, 2, 5, 99]");
+    }
+
+    #[test]
+    fn step_stops_early() {
+        // Writes 3 to cell 20 twice, then loops forever
+        let mut program = Program::default();
+        program.reset(vec![1101, 1, 2, 20, 1101, 1, 2, 20, 1105, 1, 8]);
+        let mut debugger = Debugger::new(program);
+
+        debugger.command("w 20").unwrap();
+        let status = debugger.command("s 100").unwrap();
+        assert!(status.starts_with("watch 20: 0 -> 3 by 0\n"), "{}", status);
+        assert_eq!(debugger.program.instruction_pointer, 4);
+
+        debugger.command("uw 20").unwrap();
+        debugger.command("b 8").unwrap();
+        let status = debugger.command("s 100").unwrap();
+        assert!(status.starts_with("breakpoint 8\n"), "{}", status);
+
+        debugger
+            .program
+            .set_total_budget(debugger.program.executed() + 5);
+        debugger.command("d 8").unwrap();
+        let status = debugger.command("s 100").unwrap();
+        assert!(
+            status.starts_with("instruction budget exhausted\n"),
+            "{}",
+            status
+        );
+        assert_eq!(debugger.program.remaining(), Some(0));
+    }
+
+    #[test]
+    fn list_window() {
+        let mut program = Program::default();
+        program.reset(vec![1101, 1, 2, 5, 99, 0, 1105, 1, 4]);
+        let debugger = Debugger::new(program);
+
+        assert_eq!(debugger.list(4, 2), " 0004? HLT\n 0005? 0");
+    }
+}
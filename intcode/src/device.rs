@@ -0,0 +1,185 @@
+use crate::vm::{Interupt, Program};
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender};
+
+// Source of input and sink of output for Program::run_with
+pub trait Device {
+    // The next input value, None if no input is available right now
+    fn read(&mut self) -> Option<i64>;
+    fn write(&mut self, value: i64);
+}
+
+impl Program {
+    // Runs until the program halts, faults, hits a watchpoint or the device has no input.
+    // Returns Interupt::Input in the last case, calling run_with again resumes the program.
+    pub fn run_with(&mut self, device: &mut impl Device) -> Interupt {
+        // Holds at most the one value the current input instruction is waiting for
+        let mut input = Vec::with_capacity(1);
+        loop {
+            match self.run(&mut input) {
+                Interupt::Input => match device.read() {
+                    Some(value) => input.push(value),
+                    None => return Interupt::Input,
+                },
+                Interupt::Output(value) => device.write(value),
+                interupt => return interupt,
+            }
+        }
+    }
+}
+
+// Reads input from an iterator and collects the output
+pub struct Iter<I> {
+    pub iter: I,
+    pub output: Vec<i64>,
+}
+
+impl<I: Iterator<Item = i64>> Iter<I> {
+    pub fn new(iter: impl IntoIterator<IntoIter = I>) -> Self {
+        Self {
+            iter: iter.into_iter(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = i64>> Device for Iter<I> {
+    fn read(&mut self) -> Option<i64> {
+        self.iter.next()
+    }
+    fn write(&mut self, value: i64) {
+        self.output.push(value);
+    }
+}
+
+// Connects a program to other threads, reading blocks until a value is sent.
+// Input runs out once all senders are dropped, output to a dropped receiver is discarded.
+pub struct Channel {
+    pub input: Receiver<i64>,
+    pub output: Sender<i64>,
+}
+
+impl Device for Channel {
+    fn read(&mut self) -> Option<i64> {
+        self.input.recv().ok()
+    }
+    fn write(&mut self, value: i64) {
+        let _ = self.output.send(value);
+    }
+}
+
+// Input and output queues, input can be pushed between calls to Program::run_with
+#[derive(Debug, Clone, Default)]
+pub struct RingBuffer {
+    pub input: VecDeque<i64>,
+    pub output: VecDeque<i64>,
+}
+
+impl RingBuffer {
+    pub fn new(input: impl IntoIterator<Item = i64>) -> Self {
+        Self {
+            input: input.into_iter().collect(),
+            output: VecDeque::new(),
+        }
+    }
+}
+
+impl Device for RingBuffer {
+    fn read(&mut self) -> Option<i64> {
+        self.input.pop_front()
+    }
+    fn write(&mut self, value: i64) {
+        self.output.push_back(value);
+    }
+}
+
+// Computes every input with a closure and collects the output
+pub struct Closure<F> {
+    pub read: F,
+    pub output: Vec<i64>,
+}
+
+impl<F: FnMut() -> Option<i64>> Closure<F> {
+    pub fn new(read: F) -> Self {
+        Self {
+            read,
+            output: Vec::new(),
+        }
+    }
+}
+
+impl<F: FnMut() -> Option<i64>> Device for Closure<F> {
+    fn read(&mut self) -> Option<i64> {
+        (self.read)()
+    }
+    fn write(&mut self, value: i64) {
+        self.output.push(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    // Echoes every input until the device runs out
+    const ECHO: [i64; 8] = [3, 7, 4, 7, 1105, 1, 0, 0];
+
+    fn echo() -> Program {
+        let mut program = Program::default();
+        program.reset(ECHO.to_vec());
+        program
+    }
+
+    #[test]
+    fn iter() {
+        let mut program = echo();
+        let mut device = Iter::new([1, 2, 3]);
+        assert_eq!(program.run_with(&mut device), Interupt::Input);
+        assert_eq!(device.output, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn channel() {
+        let (input, receiver) = mpsc::channel();
+        let (sender, output) = mpsc::channel();
+        let machine = thread::spawn(move || {
+            let mut program = echo();
+            program.run_with(&mut Channel {
+                input: receiver,
+                output: sender,
+            })
+        });
+
+        for value in 1..=3 {
+            input.send(value).unwrap();
+            assert_eq!(output.recv().unwrap(), value);
+        }
+        drop(input);
+        assert_eq!(machine.join().unwrap(), Interupt::Input);
+    }
+
+    #[test]
+    fn ring_buffer() {
+        let mut program = echo();
+        let mut device = RingBuffer::new([1]);
+        assert_eq!(program.run_with(&mut device), Interupt::Input);
+
+        device.input.extend([2, 3]);
+        assert_eq!(program.run_with(&mut device), Interupt::Input);
+        assert_eq!(device.output, VecDeque::from([1, 2, 3]));
+    }
+
+    #[test]
+    fn closure() {
+        let mut program = echo();
+        let mut next = 0;
+        let mut device = Closure::new(|| {
+            next += 1;
+            (next <= 3).then_some(next * 10)
+        });
+        assert_eq!(program.run_with(&mut device), Interupt::Input);
+        assert_eq!(device.output, vec![10, 20, 30]);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::vm::{Flag, Inst, Opcode};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Position(i64),
+    Immediate(i64),
+    Relative(i64),
+}
+
+impl Operand {
+    fn from(mode: u8, value: i64) -> Option<Self> {
+        match mode {
+            0 => Some(Operand::Position(value)),
+            1 => Some(Operand::Immediate(value)),
+            2 => Some(Operand::Relative(value)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Position(addr) => write!(f, "[{}]", addr),
+            Operand::Immediate(value) => write!(f, "#{}", value),
+            Operand::Relative(offset) if *offset < 0 => write!(f, "[rb{}]", offset),
+            Operand::Relative(offset) => write!(f, "[rb+{}]", offset),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub inst: Inst,
+    pub operands: Vec<Operand>,
+}
+
+impl Instruction {
+    pub fn decode(memory: &[i64], addr: usize) -> Option<Self> {
+        let opcode = Opcode::from(*memory.get(addr)?).ok()?;
+        let modes = [opcode.a(), opcode.b(), opcode.c()];
+
+        let mut operands = Vec::with_capacity(opcode.inst.params());
+        for param in modes.into_iter().take(opcode.inst.params()) {
+            let value = *memory.get(addr + param.offset)?;
+            operands.push(Operand::from(param.mode, value)?);
+        }
+
+        Some(Self {
+            inst: opcode.inst,
+            operands,
+        })
+    }
+    pub fn size(&self) -> usize {
+        self.operands.len() + 1
+    }
+    // The operand the instruction writes to, if any
+    pub fn output(&self) -> Option<&Operand> {
+        self.operands.get(self.inst.output_param()?)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.inst.mnemonic().to_uppercase())?;
+
+        let inputs = match self.output() {
+            Some(_) => &self.operands[..self.operands.len() - 1],
+            None => &self.operands[..],
+        };
+        for (idx, operand) in inputs.iter().enumerate() {
+            write!(f, "{}{}", if idx == 0 { " " } else { ", " }, operand)?;
+        }
+        if let Some(output) = self.output() {
+            write!(f, " -> {}", output)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cell {
+    // Executed instruction
+    Inst(Instruction),
+    // Never executed but decodes to a valid instruction
+    Guess(Instruction),
+    // Known to be read or written as data
+    Data(i64),
+    // Nothing is known about this cell
+    Unknown(i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub addr: usize,
+    pub cell: Cell,
+}
+
+impl Line {
+    pub fn size(&self) -> usize {
+        match &self.cell {
+            Cell::Inst(inst) | Cell::Guess(inst) => inst.size(),
+            Cell::Data(_) | Cell::Unknown(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.cell {
+            Cell::Inst(inst) => write!(f, "{:04}: {}", self.addr, inst),
+            Cell::Guess(inst) => write!(f, "{:04}? {}", self.addr, inst),
+            Cell::Data(value) => write!(f, "{:04}: DATA {}", self.addr, value),
+            Cell::Unknown(value) => write!(f, "{:04}? {}", self.addr, value),
+        }
+    }
+}
+
+// Turns a memory image into a listing using the flags collected by Program::run
+pub fn disassemble(memory: &[i64], flags: &[Flag]) -> Vec<Line> {
+    disassemble_from(memory, flags, 0, usize::MAX)
+}
+
+// At most count lines of the listing, starting at addr instead of 0
+pub fn disassemble_from(memory: &[i64], flags: &[Flag], addr: usize, count: usize) -> Vec<Line> {
+    let flag = |addr: usize| flags.get(addr).copied().unwrap_or_default();
+
+    let mut lines = Vec::new();
+    let mut addr = addr;
+    while addr < memory.len() && lines.len() < count {
+        let cell = match flag(addr) {
+            Flag::Inst => match Instruction::decode(memory, addr) {
+                Some(inst) => Cell::Inst(inst),
+                None => Cell::Unknown(memory[addr]),
+            },
+            Flag::Read | Flag::Write | Flag::ReadWrite => Cell::Data(memory[addr]),
+            Flag::Param => Cell::Unknown(memory[addr]),
+            Flag::Unflagged => match Instruction::decode(memory, addr) {
+                Some(inst)
+                    if (1..inst.size()).all(|offset| flag(addr + offset) == Flag::Unflagged) =>
+                {
+                    Cell::Guess(inst)
+                }
+                _ => Cell::Unknown(memory[addr]),
+            },
+        };
+
+        let line = Line { addr, cell };
+        addr += line.size();
+        lines.push(line);
+    }
+
+    lines
+}
+
+// Finds the instructions reachable from address 0 by following fall-through and immediate
+// jump targets, together with the addresses that start a basic block
+pub fn discover(memory: &[i64]) -> (BTreeMap<usize, Instruction>, BTreeSet<usize>) {
+    let mut instructions = BTreeMap::new();
+    let mut leaders = BTreeSet::from([0]);
+    let mut queue = vec![0];
+
+    let target = |operand: &Operand| match operand {
+        Operand::Immediate(addr) if *addr >= 0 && (*addr as usize) < memory.len() => {
+            Some(*addr as usize)
+        }
+        _ => None,
+    };
+
+    while let Some(addr) = queue.pop() {
+        if instructions.contains_key(&addr) {
+            continue;
+        }
+        let Some(inst) = Instruction::decode(memory, addr) else {
+            continue;
+        };
+
+        let next = addr + inst.size();
+        let mut successors = Vec::new();
+        match inst.inst {
+            Inst::Halt => {}
+            Inst::JumpIfTrue | Inst::JumpIfFalse => {
+                let jumps_on_true = inst.inst == Inst::JumpIfTrue;
+                let taken = match inst.operands[0] {
+                    Operand::Immediate(value) => Some((value != 0) == jumps_on_true),
+                    _ => None,
+                };
+                if taken != Some(true) {
+                    successors.push(next);
+                }
+                if taken != Some(false) {
+                    if let Some(target) = target(&inst.operands[1]) {
+                        leaders.insert(target);
+                        successors.push(target);
+                    }
+                }
+                // Whatever follows a jump is entered from the dispatch loop
+                leaders.insert(next);
+            }
+            Inst::Output => {
+                leaders.insert(next);
+                successors.push(next);
+            }
+            Inst::Input => {
+                // Execution resumes on the input instruction once input is available
+                leaders.insert(addr);
+                successors.push(next);
+            }
+            _ => successors.push(next),
+        }
+
+        instructions.insert(addr, inst);
+        queue.extend(successors);
+    }
+
+    (instructions, leaders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::{Interupt, Program};
+
+    #[test]
+    fn listing() {
+        // Jumps to 42 and adds 5 to [rb+3] into 1024, the cells after HLT are never run
+        let mut memory = vec![1105, 1, 42, 7];
+        memory.resize(42, 0);
+        memory.extend([1201, 3, 5, 1024, 99, 1101]);
+        let mut program = Program::default();
+        program.reset(memory.clone());
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Halt);
+
+        let lines = disassemble(&program.memory, &program.memory_flags)
+            .iter()
+            .filter(|line| line.addr < 4 || line.addr >= 42)
+            .map(|line| line.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            lines[..5],
+            [
+                "0000: JT #1, #42",
+                "0003: DATA 7",
+                "0042: ADD [rb+3], #5 -> [1024]",
+                "0046: HLT",
+                "0047? ADD #0, #0 -> [0]",
+            ]
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmError {
+    UnknownOpcode,
+    BadMode(u8),
+    WriteInImmediateMode,
+    NegativeAddress(i64),
+    JumpOutOfBounds(i64),
+    // Writing to the address would allocate more than the memory limit
+    MemoryLimit(i64),
+    // Write to a cell that was executed, with CodePolicy::Fault
+    CodeWrite(i64),
+    // An addition, multiplication or relative address does not fit in an i64
+    Overflow,
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmError::UnknownOpcode => write!(f, "unknown opcode"),
+            VmError::BadMode(mode) => write!(f, "unknown parameter mode {}", mode),
+            VmError::WriteInImmediateMode => write!(f, "write in immediate mode"),
+            VmError::NegativeAddress(addr) => write!(f, "negative address {}", addr),
+            VmError::JumpOutOfBounds(addr) => write!(f, "jump out of bounds to {}", addr),
+            VmError::CodeWrite(addr) => write!(f, "write to code at {}", addr),
+            VmError::MemoryLimit(addr) => write!(f, "memory limit exceeded writing to {}", addr),
+            VmError::Overflow => write!(f, "arithmetic overflow"),
+        }
+    }
+}
+
+// A VmError together with the location of the instruction that caused it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fault {
+    pub error: VmError,
+    pub instruction_pointer: usize,
+    pub opcode: i64,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (ip: {}, opcode: {})",
+            self.error, self.instruction_pointer, self.opcode
+        )
+    }
+}
+
+impl std::error::Error for Fault {}
@@ -0,0 +1,76 @@
+use crate::vm::{Interupt, Program};
+
+// Result of Program::run_until_frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame<const N: usize> {
+    Complete([i64; N]),
+    // The program halted after writing only part of a frame
+    Truncated(Vec<i64>),
+    // Any interupt other than output. Outputs of a frame in progress are kept and the frame
+    // is completed by the next call. After a fault there is no next frame, the outputs it
+    // wrote are still available from Program::pending_frame.
+    Interupt(Interupt),
+}
+
+impl Program {
+    // Runs until N outputs are written, for protocols like (x, y, tile)
+    pub fn run_until_frame<const N: usize>(&mut self, input: &mut Vec<i64>) -> Frame<N> {
+        loop {
+            match self.run(input) {
+                Interupt::Output(value) => {
+                    self.frame.push(value);
+                    if self.frame.len() == N {
+                        let values = std::array::from_fn(|idx| self.frame[idx]);
+                        self.frame.clear();
+                        return Frame::Complete(values);
+                    }
+                }
+                Interupt::Halt if !self.frame.is_empty() => {
+                    return Frame::Truncated(self.frame.drain(..).collect());
+                }
+                interupt => return Frame::Interupt(interupt),
+            }
+        }
+    }
+    // Outputs of the frame in progress
+    pub fn pending_frame(&self) -> &[i64] {
+        &self.frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::VmError;
+
+    #[test]
+    fn truncated_on_halt() {
+        let mut program = Program::default();
+        program.reset(vec![104, 1, 104, 2, 104, 3, 99]);
+        assert_eq!(
+            program.run_until_frame(&mut Vec::new()),
+            Frame::Complete([1, 2])
+        );
+        assert_eq!(
+            program.run_until_frame::<2>(&mut Vec::new()),
+            Frame::Truncated(vec![3])
+        );
+        assert_eq!(
+            program.run_until_frame::<2>(&mut Vec::new()),
+            Frame::Interupt(Interupt::Halt)
+        );
+    }
+
+    #[test]
+    fn pending_after_fault() {
+        // Outputs once, then faults on a jump with an unknown parameter mode
+        let mut program = Program::default();
+        program.reset(vec![104, 1, 3005, 0, 0]);
+        let Frame::Interupt(Interupt::Fault(fault)) = program.run_until_frame::<2>(&mut Vec::new())
+        else {
+            panic!("expected a fault");
+        };
+        assert_eq!(fault.error, VmError::BadMode(3));
+        assert_eq!(program.pending_frame(), [1]);
+    }
+}
@@ -0,0 +1,294 @@
+use crate::vm::{Flag, InputCursor, Interupt, Program};
+use std::collections::VecDeque;
+
+// Undo log of executed instructions, only recorded while enabled on a Program
+#[derive(Clone, Default)]
+pub struct Journal {
+    steps: VecDeque<Step>,
+    // Address and previous value of every memory write
+    writes: VecDeque<(usize, i64)>,
+    // Address and previous flag of every changed flag
+    flags: VecDeque<(usize, Flag)>,
+    // Writes and flags dropped together with the oldest steps, Step indices count them too
+    dropped_writes: usize,
+    dropped_flags: usize,
+    // Steps to keep, the oldest step is dropped when a new one would exceed it
+    limit: Option<usize>,
+}
+
+#[derive(Clone)]
+struct Step {
+    instruction_pointer: usize,
+    relative_base: i64,
+    memory_len: usize,
+    executed: u64,
+    // Index into Journal::writes of the first write done by this step
+    writes: usize,
+    // Index into Journal::flags of the first flag changed by this step
+    flags: usize,
+    input: Option<i64>,
+}
+
+impl Journal {
+    pub(crate) fn begin(&mut self, program: &Program) {
+        if self.limit.is_some_and(|limit| self.steps.len() >= limit) {
+            self.drop_oldest();
+        }
+        self.steps.push_back(Step {
+            instruction_pointer: program.instruction_pointer,
+            relative_base: program.relative_base,
+            memory_len: program.memory.len(),
+            executed: program.executed,
+            writes: self.dropped_writes + self.writes.len(),
+            flags: self.dropped_flags + self.flags.len(),
+            input: None,
+        });
+    }
+    // Drops the current step, used when an instruction did not execute. Flags set while
+    // decoding it stay and are restored together with the previous step.
+    pub(crate) fn cancel(&mut self) {
+        if let Some(step) = self.steps.pop_back() {
+            self.writes.truncate(step.writes - self.dropped_writes);
+        }
+    }
+    // Forgets the oldest step, it can no longer be undone
+    fn drop_oldest(&mut self) {
+        self.steps.pop_front();
+        let (writes, flags) = match self.steps.front() {
+            Some(step) => (step.writes, step.flags),
+            None => (
+                self.dropped_writes + self.writes.len(),
+                self.dropped_flags + self.flags.len(),
+            ),
+        };
+        self.writes.drain(..writes - self.dropped_writes);
+        self.flags.drain(..flags - self.dropped_flags);
+        self.dropped_writes = writes;
+        self.dropped_flags = flags;
+    }
+    // Empties the journal but keeps its limit
+    pub(crate) fn clear(&mut self) {
+        *self = Journal {
+            limit: self.limit,
+            ..Journal::default()
+        };
+    }
+    pub(crate) fn write(&mut self, addr: usize, old: i64) {
+        self.writes.push_back((addr, old));
+    }
+    pub(crate) fn flag(&mut self, addr: usize, old: Flag) {
+        self.flags.push_back((addr, old));
+    }
+    pub(crate) fn input(&mut self, value: i64) {
+        if let Some(step) = self.steps.back_mut() {
+            step.input = Some(value);
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl Program {
+    // Start recording executed instructions so they can be undone
+    pub fn enable_journal(&mut self) {
+        if self.journal.is_none() {
+            self.journal = Some(Journal::default());
+        }
+    }
+    pub fn disable_journal(&mut self) {
+        self.journal = None;
+    }
+    // Keeps only the last steps instructions in the journal, older ones can not be undone
+    pub fn set_journal_limit(&mut self, steps: usize) {
+        if let Some(journal) = &mut self.journal {
+            journal.limit = Some(steps);
+            while journal.steps.len() > steps {
+                journal.drop_oldest();
+            }
+        }
+    }
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+    // Kept out of Program::step so disabled recording costs nothing
+    pub(crate) fn step_recorded(&mut self, input: &mut InputCursor) -> Option<Interupt> {
+        let instruction_pointer = self.instruction_pointer;
+        if let Some(mut journal) = self.journal.take() {
+            journal.begin(self);
+            self.journal = Some(journal);
+        }
+
+        let result = self.exec_profiled(input);
+        if let Some(journal) = &mut self.journal {
+            if let Err(_) | Ok(Some(Interupt::Input | Interupt::Halt)) = result {
+                journal.cancel();
+            }
+        }
+
+        self.finish_step(instruction_pointer, result)
+    }
+    // Undoes the last executed instruction, consumed input is put back in front of input.
+    // Returns false if there is nothing to undo.
+    pub fn step_back(&mut self, input: &mut Vec<i64>) -> bool {
+        self.undo(input).is_some()
+    }
+    // Undoes instructions until only the first n instructions of the journal remain
+    pub fn rewind_to(&mut self, n: usize, input: &mut Vec<i64>) {
+        while self.journal.as_ref().map_or(0, Journal::len) > n {
+            self.undo(input);
+        }
+    }
+    // Undoes instructions up to and including the last one that wrote to addr, leaving the
+    // instruction pointer on that instruction. Returns false if no write to addr was recorded.
+    pub fn back_to_write(&mut self, addr: usize, input: &mut Vec<i64>) -> bool {
+        while let Some(written) = self.undo(input) {
+            if written.contains(&addr) {
+                return true;
+            }
+        }
+        false
+    }
+    // Undoes the last step and returns the addresses it wrote to
+    fn undo(&mut self, input: &mut Vec<i64>) -> Option<Vec<usize>> {
+        let journal = self.journal.as_mut()?;
+        let step = journal.steps.pop_back()?;
+
+        let writes = journal
+            .writes
+            .split_off(step.writes - journal.dropped_writes);
+        let flags = journal.flags.split_off(step.flags - journal.dropped_flags);
+        let mut written = Vec::new();
+        for (addr, old) in writes.into_iter().rev() {
+            if self.is_mapped(addr) {
+                *self.cell_mut(addr) = old;
+            }
+            written.push(addr);
+        }
+        for (addr, old) in flags.into_iter().rev() {
+            if let Some(flag) = self.flag_mut(addr) {
+                *flag = old;
+            }
+            // A cell that is no longer code is flagged again when it is decoded again
+            self.invalidate(addr);
+        }
+        // Pages allocated by the step stay, their cells are back to 0
+        self.memory.truncate(step.memory_len);
+        self.memory_flags.truncate(step.memory_len);
+        for addr in written.iter() {
+            self.invalidate(*addr);
+        }
+
+        if let Some(value) = step.input {
+            input.insert(0, value);
+        }
+        self.instruction_pointer = step.instruction_pointer;
+        self.relative_base = step.relative_base;
+        self.executed = step.executed;
+        self.halted = None;
+        self.watch_hit = None;
+
+        Some(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(memory: Vec<i64>) -> Program {
+        let mut program = Program::default();
+        program.enable_journal();
+        program.reset(memory);
+        program
+    }
+
+    #[test]
+    fn step_back_restores_flags() {
+        let mut program = program(vec![1101, 1, 2, 10, 99]);
+        let mut input = Vec::new();
+        assert_eq!(program.step(&mut input), None);
+        assert_eq!(
+            program.memory_flags[..4],
+            [Flag::Inst, Flag::Param, Flag::Param, Flag::Param]
+        );
+        assert_eq!(program.memory_flags[10], Flag::Write);
+
+        assert!(program.step_back(&mut input));
+        assert!(program
+            .memory_flags
+            .iter()
+            .all(|flag| *flag == Flag::Unflagged));
+        assert_eq!(program.peek(10), 0);
+
+        // Decoded again, so flagged again
+        assert_eq!(program.step(&mut input), None);
+        assert_eq!(program.memory_flags[0], Flag::Inst);
+        assert_eq!(program.peek(10), 3);
+    }
+
+    #[test]
+    fn rewind_self_modifying() {
+        // Writes HLT over the second output
+        let mut program = program(vec![1101, 0, 99, 6, 104, 1, 104, 2, 99]);
+        let mut input = Vec::new();
+        assert_eq!(program.run(&mut input), Interupt::Output(1));
+        assert_eq!(program.run(&mut input), Interupt::Halt);
+
+        program.rewind_to(1, &mut input);
+        assert_eq!(program.peek(6), 99);
+        assert_eq!(program.memory_flags[6], Flag::Write);
+        program.rewind_to(0, &mut input);
+        assert_eq!(program.peek(6), 104);
+        assert_eq!(program.memory_flags[6], Flag::Unflagged);
+
+        // Write to the final HLT instead, the HLT decoded at 6 before rewinding is stale
+        program.poke(3, 8).unwrap();
+        program.invalidate_cache();
+        assert_eq!(program.run(&mut input), Interupt::Output(1));
+        assert_eq!(program.run(&mut input), Interupt::Output(2));
+        assert_eq!(program.run(&mut input), Interupt::Halt);
+    }
+
+    #[test]
+    fn rewind_restores_executed() {
+        // Reads a value, outputs it and halts
+        let mut program = program(vec![3, 9, 4, 9, 99, 0, 0, 0, 0, 0]);
+        program.set_total_budget(10);
+        let mut input = vec![7];
+        assert_eq!(program.run(&mut input), Interupt::Output(7));
+        assert_eq!(program.run(&mut input), Interupt::Halt);
+        assert_eq!((program.executed(), program.remaining()), (3, Some(7)));
+
+        assert!(program.step_back(&mut input));
+        assert_eq!((program.executed(), program.remaining()), (1, Some(9)));
+        program.rewind_to(0, &mut input);
+        assert_eq!((program.executed(), program.remaining()), (0, Some(10)));
+        assert_eq!(input, vec![7]);
+
+        assert_eq!(program.run(&mut input), Interupt::Output(7));
+        assert!(program.back_to_write(9, &mut input));
+        assert_eq!(program.executed(), 0);
+    }
+
+    #[test]
+    fn limit_drops_oldest() {
+        // Counts cell 20 up forever
+        let mut program = program(vec![1001, 20, 1, 20, 1105, 1, 0]);
+        program.set_journal_limit(3);
+        for _ in 0..10 {
+            assert_eq!(program.step(&mut Vec::new()), None);
+        }
+        assert_eq!(program.journal().unwrap().len(), 3);
+        assert_eq!(program.peek(20), 5);
+
+        let mut input = Vec::new();
+        program.rewind_to(0, &mut input);
+        assert!(!program.step_back(&mut input));
+        assert_eq!(program.peek(20), 4);
+        assert_eq!(program.executed(), 7);
+    }
+}
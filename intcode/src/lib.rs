@@ -0,0 +1,48 @@
+pub mod asm;
+pub mod budget;
+pub mod cfg;
+pub mod coverage;
+pub mod debugger;
+pub mod device;
+pub mod disasm;
+pub mod error;
+pub mod frame;
+pub mod journal;
+pub mod memory;
+pub mod profile;
+pub mod scheduler;
+pub mod selfmod;
+pub mod session;
+pub mod snapshot;
+pub mod symbolic;
+pub mod taint;
+pub mod trace;
+pub mod transpile;
+pub mod vm;
+
+pub use device::Device;
+pub use error::{Fault, VmError};
+pub use frame::Frame;
+pub use vm::{Access, Flag, Inst, Interupt, Io, Opcode, ParameterMode, Program, Watchpoint};
+
+// Parses a comma separated puzzle input into a memory image
+pub fn parse(puzzle: &str) -> Vec<i64> {
+    puzzle
+        .trim()
+        .split(',')
+        .map(|code| code.trim().parse().unwrap())
+        .collect()
+}
+
+// Runs a program until it halts, faults or needs more input than it was given. Returns the
+// outputs and the interupt it stopped on, which is never an output. Panics like
+// Interupt::expect_io when a watchpoint or budget stops it.
+pub fn run_to_end(program: &mut Program, input: &mut Vec<i64>) -> (Vec<i64>, Io) {
+    let mut outputs = Vec::new();
+    loop {
+        match program.run(input).expect_io() {
+            Io::Output(value) => outputs.push(value),
+            end => return (outputs, end),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use crate::disasm::Instruction;
+use crate::error::VmError;
+use crate::vm::{Flag, Program};
+use std::collections::HashMap;
+
+// Cells per page of sparse memory
+pub const PAGE_SIZE: usize = 1024;
+// Writes below this address grow the dense memory, as long as nothing was paged yet
+pub const DENSE_LIMIT: usize = 1 << 20;
+// Allocated cells a program may use unless Program::set_memory_limit says otherwise, 512 MiB
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 26;
+
+// Memory above the dense Program::memory.
+//
+// Program::memory grows like a Vec for writes just past its end. A write far beyond it, or
+// any write after the first page was allocated, goes to a page instead so a program that
+// writes to address 10^12 allocates one page rather than terabytes. Dense memory never grows
+// once there are pages, so an address always lives in exactly one of the two.
+#[derive(Clone, Default)]
+pub struct Pages {
+    pages: HashMap<usize, Page>,
+}
+
+#[derive(Clone)]
+struct Page {
+    values: Box<[i64; PAGE_SIZE]>,
+    flags: Box<[Flag; PAGE_SIZE]>,
+}
+
+impl Pages {
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+    // Values of every allocated page by the address of its first cell, sorted by address
+    pub fn values(&self) -> Vec<(usize, &[i64])> {
+        let mut pages = self
+            .pages
+            .iter()
+            .map(|(nr, page)| (nr * PAGE_SIZE, &page.values[..]))
+            .collect::<Vec<(usize, &[i64])>>();
+        pages.sort_by_key(|(addr, _)| *addr);
+        pages
+    }
+    // Flags of every allocated page like Pages::values
+    pub fn flags(&self) -> Vec<(usize, &[Flag])> {
+        let mut pages = self
+            .pages
+            .iter()
+            .map(|(nr, page)| (nr * PAGE_SIZE, &page.flags[..]))
+            .collect::<Vec<(usize, &[Flag])>>();
+        pages.sort_by_key(|(addr, _)| *addr);
+        pages
+    }
+    fn get(&self, addr: usize) -> Option<&Page> {
+        self.pages.get(&(addr / PAGE_SIZE))
+    }
+    fn get_mut(&mut self, addr: usize) -> Option<&mut Page> {
+        self.pages.get_mut(&(addr / PAGE_SIZE))
+    }
+    fn clear(&mut self) {
+        self.pages.clear();
+    }
+}
+
+impl Program {
+    pub fn set_memory_limit(&mut self, cells: usize) {
+        self.memory_limit = Some(cells);
+    }
+    pub fn memory_limit(&self) -> usize {
+        self.memory_limit.unwrap_or(DEFAULT_MEMORY_LIMIT)
+    }
+    // Cells of dense memory and pages together
+    pub fn allocated(&self) -> usize {
+        self.memory.len() + self.pages.len() * PAGE_SIZE
+    }
+    pub fn pages(&self) -> &Pages {
+        &self.pages
+    }
+    // Whether addr is backed by memory, unallocated addresses read as 0
+    pub fn is_mapped(&self, addr: usize) -> bool {
+        addr < self.memory.len() || self.pages.get(addr).is_some()
+    }
+    // Whether a write to addr stays within the memory limit
+    pub fn is_writable(&self, addr: usize) -> bool {
+        self.is_mapped(addr) || self.allocation(addr).is_some()
+    }
+    // Reads addr without flagging it or checking watchpoints
+    pub fn peek(&self, addr: usize) -> i64 {
+        match self.memory.get(addr) {
+            Some(value) => *value,
+            None => self
+                .pages
+                .get(addr)
+                .map_or(0, |page| page.values[addr % PAGE_SIZE]),
+        }
+    }
+    // Decodes the instruction at addr from dense memory or a page
+    pub fn instruction_at(&self, addr: usize) -> Option<Instruction> {
+        let cells = [0, 1, 2, 3].map(|offset| self.peek(addr + offset));
+        Instruction::decode(&cells, 0)
+    }
+    // Writes addr without flagging it, checking watchpoints or recording it in the journal.
+    // Must be followed by Program::invalidate_cache if addr holds code.
+    pub fn poke(&mut self, addr: usize, value: i64) -> Result<(), VmError> {
+        self.allocate(addr)?;
+        *self.cell_mut(addr) = value;
+        Ok(())
+    }
+    pub(crate) fn clear_pages(&mut self) {
+        self.pages.clear();
+    }
+    // Restores a page saved from Pages::values, addr is the address of its first cell.
+    // Returns false without loading anything if addr is not the start of a page or the values
+    // do not fit in one.
+    pub(crate) fn load_page(&mut self, addr: usize, values: &[i64]) -> bool {
+        if !addr.is_multiple_of(PAGE_SIZE) || values.len() > PAGE_SIZE {
+            return false;
+        }
+        let mut page = Page {
+            values: Box::new([0; PAGE_SIZE]),
+            flags: Box::new([Flag::Unflagged; PAGE_SIZE]),
+        };
+        page.values[..values.len()].copy_from_slice(values);
+        self.pages.pages.insert(addr / PAGE_SIZE, page);
+        true
+    }
+    // Restores the flags of a page loaded with Program::load_page. Returns false if there is
+    // no page at addr or the flags do not fit in one.
+    pub(crate) fn load_page_flags(&mut self, addr: usize, flags: &[Flag]) -> bool {
+        if !addr.is_multiple_of(PAGE_SIZE) || flags.len() > PAGE_SIZE {
+            return false;
+        }
+        match self.pages.get_mut(addr) {
+            Some(page) => {
+                page.flags[..flags.len()].copy_from_slice(flags);
+                true
+            }
+            None => false,
+        }
+    }
+    // New length of dense memory or a new page for a write to addr, None if that would
+    // exceed the memory limit
+    fn allocation(&self, addr: usize) -> Option<Allocation> {
+        if self.pages.is_empty() && addr < DENSE_LIMIT {
+            let len = addr + 100;
+            return (len <= self.memory_limit()).then_some(Allocation::Dense(len));
+        }
+        (self.allocated() + PAGE_SIZE <= self.memory_limit()).then_some(Allocation::Page)
+    }
+    // Makes sure addr is backed by memory
+    pub(crate) fn allocate(&mut self, addr: usize) -> Result<(), VmError> {
+        if self.is_mapped(addr) {
+            return Ok(());
+        }
+        match self.allocation(addr) {
+            Some(Allocation::Dense(len)) => {
+                self.memory.resize(len, 0);
+            }
+            Some(Allocation::Page) => {
+                self.load_page(addr / PAGE_SIZE * PAGE_SIZE, &[]);
+            }
+            None => return Err(VmError::MemoryLimit(addr as i64)),
+        }
+        Ok(())
+    }
+    // Cell of an allocated address
+    pub(crate) fn cell_mut(&mut self, addr: usize) -> &mut i64 {
+        if addr < self.memory.len() {
+            return &mut self.memory[addr];
+        }
+        &mut self.pages.get_mut(addr).unwrap().values[addr % PAGE_SIZE]
+    }
+    // Flag of addr, None for unallocated memory
+    pub(crate) fn flag_mut(&mut self, addr: usize) -> Option<&mut Flag> {
+        if addr < self.memory.len() {
+            if addr >= self.memory_flags.len() {
+                self.memory_flags.resize(addr + 1, Flag::Unflagged);
+            }
+            return Some(&mut self.memory_flags[addr]);
+        }
+        self.pages
+            .get_mut(addr)
+            .map(|page| &mut page.flags[addr % PAGE_SIZE])
+    }
+}
+
+enum Allocation {
+    Dense(usize),
+    Page,
+}
@@ -0,0 +1,220 @@
+use crate::error::VmError;
+use crate::vm::{InputCursor, Inst, Interupt, Opcode, Program};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// Execution counts, only recorded while enabled on a Program
+#[derive(Clone, Default)]
+pub struct Profile {
+    // Times the instruction at each executed address was executed
+    pub hits: BTreeMap<usize, u64>,
+    // Executed instructions per Inst, in the order of Inst::ALL
+    pub opcodes: [u64; 10],
+    // Per address of a jump instruction, a jump to the next instruction counts as not taken
+    pub taken: BTreeMap<usize, u64>,
+    pub not_taken: BTreeMap<usize, u64>,
+    pub instructions: u64,
+    // Executed instructions since each Program::reset, the last one is the current run. The
+    // other counts only cover the current run.
+    pub runs: Vec<u64>,
+}
+
+impl Profile {
+    pub fn jumps_taken(&self) -> u64 {
+        self.taken.values().sum()
+    }
+    pub fn jumps_not_taken(&self) -> u64 {
+        self.not_taken.values().sum()
+    }
+    // Clears the counts for a new run, keeping the earlier runs
+    pub(crate) fn restart(&mut self) {
+        let mut runs = std::mem::take(&mut self.runs);
+        runs.push(0);
+        *self = Profile {
+            runs,
+            ..Profile::default()
+        };
+    }
+    fn record(&mut self, addr: usize, inst: Inst, jumped: Option<bool>) {
+        *self.hits.entry(addr).or_default() += 1;
+        self.opcodes[inst as usize] += 1;
+        match jumped {
+            Some(true) => *self.taken.entry(addr).or_default() += 1,
+            Some(false) => *self.not_taken.entry(addr).or_default() += 1,
+            None => {}
+        }
+        self.instructions += 1;
+        match self.runs.last_mut() {
+            Some(run) => *run += 1,
+            None => self.runs.push(1),
+        }
+    }
+    // Summary followed by the limit most executed instructions
+    pub fn report(&self, program: &Program, limit: usize) -> String {
+        let mut report = String::new();
+        writeln!(report, "instructions: {}", self.instructions).unwrap();
+        writeln!(report, "runs: {:?}", self.runs).unwrap();
+        writeln!(
+            report,
+            "jumps: {} taken, {} not taken",
+            self.jumps_taken(),
+            self.jumps_not_taken()
+        )
+        .unwrap();
+
+        writeln!(report, "opcodes:").unwrap();
+        let mut opcodes = Inst::ALL
+            .iter()
+            .zip(self.opcodes)
+            .filter(|(_, count)| *count > 0)
+            .collect::<Vec<(&Inst, u64)>>();
+        opcodes.sort_by_key(|(_, count)| u64::MAX - count);
+        for (inst, count) in opcodes {
+            writeln!(
+                report,
+                "  {:<4}{:>12} {:>6.2}%",
+                inst.mnemonic(),
+                count,
+                self.percentage(count)
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "hot spots:").unwrap();
+        for (addr, hits) in self.hot_spots().into_iter().take(limit) {
+            writeln!(
+                report,
+                "  {:>12} {:>6.2}%  {:04}: {}",
+                hits,
+                self.percentage(hits),
+                addr,
+                disassemble(program, addr)
+            )
+            .unwrap();
+        }
+
+        report
+    }
+    // Executed addresses sorted by hits, the most executed first
+    pub fn hot_spots(&self) -> Vec<(usize, u64)> {
+        let mut hot_spots = self
+            .hits
+            .iter()
+            .map(|(addr, hits)| (*addr, *hits))
+            .collect::<Vec<(usize, u64)>>();
+        hot_spots.sort_by_key(|(addr, hits)| (u64::MAX - hits, *addr));
+        hot_spots
+    }
+    // One line per executed address with its counts and disassembly
+    pub fn csv(&self, program: &Program) -> String {
+        let mut csv = "addr,hits,taken,not_taken,instruction\n".to_string();
+        for (addr, hits) in self.hits.iter() {
+            writeln!(
+                csv,
+                "{},{},{},{},\"{}\"",
+                addr,
+                hits,
+                self.taken.get(addr).unwrap_or(&0),
+                self.not_taken.get(addr).unwrap_or(&0),
+                disassemble(program, *addr)
+            )
+            .unwrap();
+        }
+        csv
+    }
+    fn percentage(&self, count: u64) -> f64 {
+        if self.instructions == 0 {
+            return 0.0;
+        }
+        count as f64 * 100.0 / self.instructions as f64
+    }
+}
+
+fn disassemble(program: &Program, addr: usize) -> String {
+    match program.instruction_at(addr) {
+        Some(inst) => inst.to_string(),
+        None => format!("DATA {}", program.peek(addr)),
+    }
+}
+
+impl Program {
+    pub fn enable_profile(&mut self) {
+        if self.profile.is_none() {
+            self.profile = Some(Profile::default());
+        }
+    }
+    pub fn disable_profile(&mut self) {
+        self.profile = None;
+    }
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+    // Program::exec that also records the instruction if profiling is enabled
+    pub(crate) fn exec_profiled(
+        &mut self,
+        input: &mut InputCursor,
+    ) -> Result<Option<Interupt>, VmError> {
+        if self.profile.is_none() {
+            return self.exec_tainted(input);
+        }
+
+        // Decoded up front, the instruction might overwrite itself
+        let instruction_pointer = self.instruction_pointer;
+        let opcode = Opcode::from(self.peek(instruction_pointer));
+
+        let result = self.exec_tainted(input);
+        if let (Ok(opcode), Ok(None | Some(Interupt::Output(_) | Interupt::Halt))) =
+            (opcode, &result)
+        {
+            let jumped = match opcode.inst {
+                Inst::JumpIfTrue | Inst::JumpIfFalse => {
+                    Some(self.instruction_pointer != instruction_pointer + 3)
+                }
+                _ => None,
+            };
+            if let Some(profile) = &mut self.profile {
+                profile.record(instruction_pointer, opcode.inst, jumped);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_in_page() {
+        // Writes HLT to a page and jumps to it
+        let mut program = Program::default();
+        program.enable_profile();
+        program.reset(vec![1101, 0, 99, 1 << 40, 1105, 1, 1 << 40]);
+
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Halt);
+        let profile = program.profile().unwrap();
+        assert_eq!(profile.instructions, 3);
+        assert_eq!(profile.hits.get(&(1 << 40)), Some(&1));
+        assert_eq!(profile.taken.get(&4), Some(&1));
+        assert!(profile
+            .csv(&program)
+            .ends_with(&format!("{},1,0,0,\"HLT\"\n", 1u64 << 40)));
+    }
+
+    #[test]
+    fn runs_per_reset() {
+        // Outputs twice and halts
+        let image = vec![104, 1, 104, 2, 99];
+        let mut program = Program::default();
+        program.enable_profile();
+        for _ in 0..2 {
+            program.reset(image.clone());
+            assert_eq!(program.run(&mut Vec::new()), Interupt::Output(1));
+            assert_eq!(program.run(&mut Vec::new()), Interupt::Output(2));
+            assert_eq!(program.run(&mut Vec::new()), Interupt::Halt);
+        }
+        let profile = program.profile().unwrap();
+        assert_eq!(profile.runs, [3, 3]);
+        assert_eq!(profile.instructions, 3);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::error::Fault;
+use crate::vm::{Interupt, Program};
+use std::collections::VecDeque;
+
+// Runs several programs connected by named queues.
+//
+// Every machine reads from one queue and writes each output to all of its output queues, so
+// chains, feedback loops and broadcasts are all a matter of naming the right queues. A queue
+// without a reader collects results. Queues are created the first time they are named.
+#[derive(Default)]
+pub struct Scheduler {
+    machines: Vec<Machine>,
+    queues: Vec<Queue>,
+    last_halted: Option<usize>,
+}
+
+struct Machine {
+    name: String,
+    program: Program,
+    input: usize,
+    outputs: Vec<usize>,
+    halted: bool,
+}
+
+struct Queue {
+    name: String,
+    values: VecDeque<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    // Every machine halted, last is the name of the machine that halted last
+    Halted { last: String },
+    // Every machine that did not halt waits for input on an empty queue
+    Deadlock { waiting: Vec<String> },
+    Fault { machine: String, fault: Fault },
+    // A watchpoint was hit, calling run again continues
+    Watch { machine: String, interupt: Interupt },
+    // The machine ran out of its instruction budget, calling run again continues
+    BudgetExhausted { machine: String },
+}
+
+impl Scheduler {
+    pub fn add(&mut self, name: &str, program: Program, input: &str, outputs: &[&str]) {
+        let input = self.queue_idx(input);
+        let outputs = outputs.iter().map(|name| self.queue_idx(name)).collect();
+        self.machines.push(Machine {
+            name: name.to_string(),
+            program,
+            input,
+            outputs,
+            halted: false,
+        });
+    }
+    pub fn push(&mut self, queue: &str, value: i64) {
+        let idx = self.queue_idx(queue);
+        self.queues[idx].values.push_back(value);
+    }
+    pub fn queue(&self, name: &str) -> Option<&VecDeque<i64>> {
+        self.queues
+            .iter()
+            .find(|queue| queue.name == name)
+            .map(|queue| &queue.values)
+    }
+    pub fn program(&self, name: &str) -> Option<&Program> {
+        self.machines
+            .iter()
+            .find(|machine| machine.name == name)
+            .map(|machine| &machine.program)
+    }
+    pub fn program_mut(&mut self, name: &str) -> Option<&mut Program> {
+        self.machines
+            .iter_mut()
+            .find(|machine| machine.name == name)
+            .map(|machine| &mut machine.program)
+    }
+    // Name of the machine that halted most recently
+    pub fn last_halted(&self) -> Option<&str> {
+        self.last_halted.map(|idx| self.machines[idx].name.as_str())
+    }
+    // Runs the machines in turn, each one until it halts or waits on an empty queue
+    pub fn run(&mut self) -> Outcome {
+        let mut input = Vec::with_capacity(1);
+        loop {
+            for idx in 0..self.machines.len() {
+                if self.machines[idx].halted {
+                    continue;
+                }
+
+                loop {
+                    let machine = &mut self.machines[idx];
+                    match machine.program.run(&mut input) {
+                        Interupt::Input => match self.queues[machine.input].values.pop_front() {
+                            Some(value) => input.push(value),
+                            None => break,
+                        },
+                        Interupt::Output(value) => {
+                            for output in machine.outputs.iter() {
+                                self.queues[*output].values.push_back(value);
+                            }
+                        }
+                        Interupt::Halt => {
+                            machine.halted = true;
+                            self.last_halted = Some(idx);
+                            break;
+                        }
+                        Interupt::Fault(fault) => {
+                            return Outcome::Fault {
+                                machine: machine.name.clone(),
+                                fault,
+                            };
+                        }
+                        interupt @ Interupt::Watch { .. } => {
+                            return Outcome::Watch {
+                                machine: machine.name.clone(),
+                                interupt,
+                            };
+                        }
+                        Interupt::BudgetExhausted => {
+                            return Outcome::BudgetExhausted {
+                                machine: machine.name.clone(),
+                            };
+                        }
+                    }
+                }
+            }
+
+            let running = self.machines.iter().filter(|machine| !machine.halted);
+            if running.clone().next().is_none() {
+                return Outcome::Halted {
+                    last: self.last_halted().unwrap_or_default().to_string(),
+                };
+            }
+            // A machine can only be woken up by another machine writing to its queue
+            if running
+                .clone()
+                .all(|machine| self.queues[machine.input].values.is_empty())
+            {
+                return Outcome::Deadlock {
+                    waiting: running.map(|machine| machine.name.clone()).collect(),
+                };
+            }
+        }
+    }
+    fn queue_idx(&mut self, name: &str) -> usize {
+        match self.queues.iter().position(|queue| queue.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.queues.push(Queue {
+                    name: name.to_string(),
+                    values: VecDeque::new(),
+                });
+                self.queues.len() - 1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Read a value, write it doubled or incremented and halt
+    const DOUBLE: [i64; 10] = [3, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+    const INCREMENT: [i64; 10] = [3, 9, 1001, 9, 1, 9, 4, 9, 99, 0];
+
+    fn program(memory: &[i64]) -> Program {
+        let mut program = Program::default();
+        program.reset(memory.to_vec());
+        program
+    }
+
+    #[test]
+    fn chain_halts() {
+        let mut scheduler = Scheduler::default();
+        // Added in reverse so increment first waits on an empty queue
+        scheduler.add("increment", program(&INCREMENT), "doubled", &["out", "log"]);
+        scheduler.add("double", program(&DOUBLE), "in", &["doubled"]);
+        scheduler.push("in", 5);
+
+        assert_eq!(
+            scheduler.run(),
+            Outcome::Halted {
+                last: "increment".to_string()
+            }
+        );
+        assert_eq!(scheduler.queue("out"), Some(&VecDeque::from([11])));
+        assert_eq!(scheduler.queue("log"), Some(&VecDeque::from([11])));
+    }
+
+    #[test]
+    fn deadlock() {
+        let mut scheduler = Scheduler::default();
+        scheduler.add("a", program(&DOUBLE), "b to a", &["a to b"]);
+        scheduler.add("b", program(&DOUBLE), "a to b", &["b to a"]);
+        scheduler.add("c", program(&[99]), "unused", &[]);
+
+        assert_eq!(
+            scheduler.run(),
+            Outcome::Deadlock {
+                waiting: vec!["a".to_string(), "b".to_string()]
+            }
+        );
+        assert_eq!(scheduler.last_halted(), Some("c"));
+
+        // A value breaks the cycle, a doubles it for b and b doubles it back
+        scheduler.push("b to a", 3);
+        assert_eq!(
+            scheduler.run(),
+            Outcome::Halted {
+                last: "b".to_string()
+            }
+        );
+        assert_eq!(scheduler.queue("b to a"), Some(&VecDeque::from([12])));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::disasm::Instruction;
+use crate::error::VmError;
+use crate::vm::Program;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+// What a program may do with cells that were already executed as an instruction or operand.
+// Cells are only code once executed, patching code before it first runs is not detected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CodePolicy {
+    #[default]
+    Allow,
+    // Allow the write but record it in Program::code_writes
+    Warn,
+    // Fault with VmError::CodeWrite before the write happens
+    Fault,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeWrite {
+    // Instruction that did the write
+    pub ip: usize,
+    pub addr: usize,
+    pub old: i64,
+    pub new: i64,
+}
+
+// Instruction that wrote over code, with every code cell it wrote to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Site {
+    pub writes: usize,
+    pub addrs: BTreeSet<usize>,
+}
+
+impl Program {
+    pub fn set_code_policy(&mut self, policy: CodePolicy) {
+        self.code_policy = policy;
+    }
+    pub fn code_policy(&self) -> CodePolicy {
+        self.code_policy
+    }
+    // Writes to code since the last reset, only recorded with CodePolicy::Warn or Fault
+    pub fn code_writes(&self) -> &[CodeWrite] {
+        &self.code_writes
+    }
+    pub fn is_self_modifying(&self) -> bool {
+        !self.code_writes.is_empty()
+    }
+    // Recorded code writes grouped by the instruction that did them
+    pub fn self_modifying_sites(&self) -> BTreeMap<usize, Site> {
+        let mut sites: BTreeMap<usize, Site> = BTreeMap::new();
+        for write in self.code_writes.iter() {
+            let site = sites.entry(write.ip).or_default();
+            site.writes += 1;
+            site.addrs.insert(write.addr);
+        }
+        sites
+    }
+    // One line per self modifying site followed by its distinct writes
+    pub fn self_modify_report(&self) -> String {
+        let mut report = String::new();
+        let sites = self.self_modifying_sites();
+        writeln!(
+            report,
+            "code writes: {} by {} sites",
+            self.code_writes.len(),
+            sites.len()
+        )
+        .unwrap();
+
+        for (ip, site) in sites {
+            // The current code, the site might have rewritten itself
+            let inst = Instruction::decode(&self.memory, ip)
+                .map(|inst| inst.to_string())
+                .unwrap_or_else(|| format!("DATA {}", self.peek(ip)));
+            let addrs = site
+                .addrs
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<String>>();
+            writeln!(
+                report,
+                "  {:04}: {:<24} {} writes to {}",
+                ip,
+                inst,
+                site.writes,
+                addrs.join(", ")
+            )
+            .unwrap();
+
+            let mut seen = BTreeSet::new();
+            for write in self.code_writes.iter().filter(|write| write.ip == ip) {
+                if seen.insert((write.addr, write.old, write.new)) {
+                    writeln!(
+                        report,
+                        "        {:04}: {} -> {}",
+                        write.addr, write.old, write.new
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        report
+    }
+    // Called for every write to a code cell, before the write happens
+    pub(crate) fn code_write(&mut self, addr: usize, new: i64) -> Result<(), VmError> {
+        if self.code_policy == CodePolicy::Allow {
+            return Ok(());
+        }
+
+        let write = CodeWrite {
+            ip: self.instruction_pointer,
+            addr,
+            old: self.peek(addr),
+            new,
+        };
+        // A faulting write runs again when the program is resumed
+        if self.code_writes.last() != Some(&write) {
+            self.code_writes.push(write);
+        }
+        match self.code_policy {
+            CodePolicy::Fault => Err(VmError::CodeWrite(addr as i64)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::Interupt;
+
+    // Outputs 1, writes HLT over the output and jumps back to it
+    const IMAGE: [i64; 9] = [104, 1, 1101, 0, 99, 0, 1105, 1, 0];
+
+    #[test]
+    fn fault_stops_before_write() {
+        let mut program = Program::default();
+        program.set_code_policy(CodePolicy::Fault);
+        program.reset(IMAGE.to_vec());
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Output(1));
+        for _ in 0..2 {
+            let Interupt::Fault(fault) = program.run(&mut Vec::new()) else {
+                panic!("expected a fault");
+            };
+            assert_eq!(fault.error, VmError::CodeWrite(0));
+            assert_eq!(fault.instruction_pointer, 2);
+        }
+        assert_eq!(program.peek(0), 104);
+        assert_eq!(program.instruction_pointer, 2);
+        assert_eq!(
+            program.code_writes(),
+            [CodeWrite {
+                ip: 2,
+                addr: 0,
+                old: 104,
+                new: 99
+            }]
+        );
+    }
+
+    #[test]
+    fn warn_records_sites() {
+        let mut program = Program::default();
+        program.set_code_policy(CodePolicy::Warn);
+        program.reset(IMAGE.to_vec());
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Output(1));
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Halt);
+
+        assert!(program.is_self_modifying());
+        let sites = program.self_modifying_sites();
+        assert_eq!(sites.keys().copied().collect::<Vec<usize>>(), [2]);
+        assert_eq!(sites[&2].addrs, BTreeSet::from([0]));
+    }
+}
@@ -0,0 +1,313 @@
+use crate::vm::{Interupt, Program};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+const MAGIC: &str = "intcode-session";
+const VERSION: u32 = 1;
+
+// Every input value a program consumed, with the state it started from, so an interactive
+// run can be replayed without its driver.
+//
+// The file format is line based like snapshots, the first line is the magic and version:
+//
+//     intcode-session 1
+//     instruction_pointer 0
+//     relative_base 0
+//     memory 2,0,0,99
+//     input 1520 -1
+//     input 1894 0
+//
+// Every input line holds the instructions executed since the session started when the value
+// was consumed, followed by the value. Sparse memory pages are not part of the session.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Session {
+    pub instruction_pointer: usize,
+    pub relative_base: i64,
+    pub memory: Vec<i64>,
+    pub inputs: Vec<(u64, i64)>,
+    // Program::executed when the session started
+    start: u64,
+}
+
+// Result of replaying a session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub outputs: Vec<i64>,
+    // Halt, a fault, or Input if the program wants more than the session holds
+    pub end: Interupt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    // Input idx was requested after a different number of instructions than recorded
+    Diverged {
+        input: usize,
+        expected: u64,
+        executed: u64,
+    },
+    // The program stopped before consuming every input of the session
+    Unconsumed {
+        consumed: usize,
+        end: Interupt,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Diverged {
+                input,
+                expected,
+                executed,
+            } => write!(
+                f,
+                "input {} requested after {} instructions, recorded after {}",
+                input, executed, expected
+            ),
+            ReplayError::Unconsumed { consumed, end } => {
+                write!(
+                    f,
+                    "stopped with {:?} after consuming {} inputs",
+                    end, consumed
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl Session {
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SessionError> {
+        fs::read_to_string(path)?.parse()
+    }
+    // The input values in the order they were consumed
+    pub fn values(&self) -> Vec<i64> {
+        self.inputs.iter().map(|(_, value)| *value).collect()
+    }
+    // A program in the state the session started from
+    pub fn program(&self) -> Program {
+        let mut program = Program::default();
+        program.reset(self.memory.clone());
+        program.instruction_pointer = self.instruction_pointer;
+        program.relative_base = self.relative_base;
+        program
+    }
+    // Runs the program from the start of the session, feeding every input at the moment it was
+    // recorded
+    pub fn replay(&self) -> Result<Replay, ReplayError> {
+        let mut program = self.program();
+        let mut outputs = Vec::new();
+        let mut input = Vec::with_capacity(1);
+        let mut consumed = 0;
+
+        loop {
+            match program.run(&mut input) {
+                Interupt::Output(value) => outputs.push(value),
+                Interupt::Input if consumed < self.inputs.len() => {
+                    let (expected, value) = self.inputs[consumed];
+                    if program.executed() != expected {
+                        return Err(ReplayError::Diverged {
+                            input: consumed,
+                            expected,
+                            executed: program.executed(),
+                        });
+                    }
+                    input.push(value);
+                    consumed += 1;
+                }
+                end if consumed < self.inputs.len() => {
+                    return Err(ReplayError::Unconsumed { consumed, end });
+                }
+                end => return Ok(Replay { outputs, end }),
+            }
+        }
+    }
+    pub(crate) fn record(&mut self, executed: u64, value: i64) {
+        self.inputs.push((executed - self.start, value));
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} {}", MAGIC, VERSION)?;
+        writeln!(f, "instruction_pointer {}", self.instruction_pointer)?;
+        writeln!(f, "relative_base {}", self.relative_base)?;
+        let memory = self
+            .memory
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<String>>();
+        writeln!(f, "memory {}", memory.join(","))?;
+        for (executed, value) in self.inputs.iter() {
+            writeln!(f, "input {} {}", executed, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Session {
+    type Err = SessionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+
+        let header = lines.next().unwrap_or("");
+        let version = match header.split_once(' ') {
+            Some((MAGIC, version)) => version
+                .trim()
+                .parse()
+                .map_err(|_| SessionError::Malformed(1, "version".to_string()))?,
+            _ => return Err(SessionError::NotASession),
+        };
+        if version > VERSION {
+            return Err(SessionError::UnsupportedVersion(version));
+        }
+
+        let mut session = Session::default();
+        for (idx, line) in lines.enumerate() {
+            let line_nr = idx + 2;
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let malformed = || SessionError::Malformed(line_nr, key.to_string());
+
+            match key {
+                "instruction_pointer" => {
+                    session.instruction_pointer = value.parse().map_err(|_| malformed())?
+                }
+                "relative_base" => {
+                    session.relative_base = value.parse().map_err(|_| malformed())?
+                }
+                "memory" if value.trim().is_empty() => session.memory = Vec::new(),
+                "memory" => {
+                    session.memory = value
+                        .split(',')
+                        .map(|value| value.trim().parse().ok())
+                        .collect::<Option<Vec<i64>>>()
+                        .ok_or_else(malformed)?
+                }
+                "input" => {
+                    let (executed, value) = value.split_once(' ').ok_or_else(malformed)?;
+                    let executed = executed.parse().map_err(|_| malformed())?;
+                    let value = value.trim().parse().map_err(|_| malformed())?;
+                    session.inputs.push((executed, value));
+                }
+                _ => {} // Written by a newer version, ignore
+            }
+        }
+
+        Ok(session)
+    }
+}
+
+#[derive(Debug)]
+pub enum SessionError {
+    Io(io::Error),
+    NotASession,
+    UnsupportedVersion(u32),
+    // Line number and key of a value that could not be parsed
+    Malformed(usize, String),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::Io(err) => write!(f, "{}", err),
+            SessionError::NotASession => write!(f, "not an intcode session"),
+            SessionError::UnsupportedVersion(version) => {
+                write!(f, "unsupported session version {}", version)
+            }
+            SessionError::Malformed(line, key) => {
+                write!(f, "line {}: malformed value for {}", line, key)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl From<io::Error> for SessionError {
+    fn from(err: io::Error) -> Self {
+        SessionError::Io(err)
+    }
+}
+
+impl Program {
+    // Starts recording consumed input from the current state, call it after patching memory.
+    // Program::reset starts a new session from the new memory.
+    pub fn start_session(&mut self) {
+        self.session = Some(Session {
+            instruction_pointer: self.instruction_pointer,
+            relative_base: self.relative_base,
+            memory: self.memory.clone(),
+            inputs: Vec::new(),
+            start: self.executed,
+        });
+    }
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+    pub fn stop_session(&mut self) -> Option<Session> {
+        self.session.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sums the inputs up to the first 0 and outputs the sum
+    fn program() -> Program {
+        let mut program = Program::default();
+        program.reset(vec![
+            3, 20, 1006, 20, 12, 1, 20, 21, 21, 1105, 1, 0, 4, 21, 99,
+        ]);
+        program
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let mut program = program();
+        // Recording starts after the patch, so it is part of the session memory
+        program.poke(21, 10).unwrap();
+        program.start_session();
+        let mut input = Vec::new();
+        for value in [2, 3, 0] {
+            assert_eq!(program.run(&mut input), Interupt::Input);
+            input.push(value);
+        }
+        assert_eq!(program.run(&mut input), Interupt::Output(15));
+        assert_eq!(program.run(&mut input), Interupt::Halt);
+
+        let session: Session = program.session().unwrap().to_string().parse().unwrap();
+        assert_eq!(session.values(), [2, 3, 0]);
+        assert_eq!(session.inputs[0].0, 0);
+        assert_eq!(
+            session.replay(),
+            Ok(Replay {
+                outputs: vec![15],
+                end: Interupt::Halt
+            })
+        );
+
+        let mut diverged = session.clone();
+        diverged.inputs[1].0 += 1;
+        assert_eq!(
+            diverged.replay(),
+            Err(ReplayError::Diverged {
+                input: 1,
+                expected: session.inputs[1].0 + 1,
+                executed: session.inputs[1].0
+            })
+        );
+
+        let mut short = session;
+        short.inputs.pop();
+        assert_eq!(short.replay().unwrap().end, Interupt::Input);
+    }
+}
@@ -0,0 +1,395 @@
+use crate::memory::PAGE_SIZE;
+use crate::selfmod::CodePolicy;
+use crate::vm::{Access, Flag, Program, Watchpoint};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+const MAGIC: &str = "intcode-snapshot";
+const VERSION: u32 = 1;
+
+// Saved state of a program and its pending input.
+//
+// The file format is line based, the first line is the magic and version followed by one
+// `key value` line per field:
+//
+//     intcode-snapshot 1
+//     instruction_pointer 42
+//     relative_base 1000
+//     memory 1,2,3
+//     memory_flags IPP
+//     page 1048576 0,0,7
+//     page_flags 1048576 ..W
+//     memory_limit 4096
+//     executed 1520
+//     halted 40
+//     code_policy warn
+//     run_budget 1000
+//     total_budget 100000
+//     watch 20 24 rw
+//     input 5
+//
+// There is one page and page_flags line per page of sparse memory, trailing zeros and
+// unflagged cells are left out. There is one watch line per watchpoint with the start and end
+// of its range and r, w or rw. memory_limit, halted and the budgets are only written when
+// they were set.
+// Recorders like the journal, profile, taint, trace and session are not part of a snapshot.
+// Unknown keys are ignored and missing keys keep their default value so snapshots keep
+// loading when fields are added or removed.
+#[derive(Clone, Default)]
+pub struct Snapshot {
+    pub program: Program,
+    pub input: Vec<i64>,
+}
+
+impl Snapshot {
+    pub fn new(program: &Program, input: &[i64]) -> Self {
+        Self {
+            program: program.clone(),
+            input: input.to_vec(),
+        }
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let program = &self.program;
+        writeln!(f, "{} {}", MAGIC, VERSION)?;
+        writeln!(f, "instruction_pointer {}", program.instruction_pointer)?;
+        writeln!(f, "relative_base {}", program.relative_base)?;
+        writeln!(f, "memory {}", join(&program.memory))?;
+        let flags: String = program.memory_flags.iter().map(flag_to_char).collect();
+        writeln!(f, "memory_flags {}", flags)?;
+        for (addr, values) in program.pages().values() {
+            let len = values
+                .iter()
+                .rposition(|value| *value != 0)
+                .map_or(0, |idx| idx + 1);
+            writeln!(f, "page {} {}", addr, join(&values[..len]))?;
+        }
+        for (addr, flags) in program.pages().flags() {
+            let len = flags
+                .iter()
+                .rposition(|flag| *flag != Flag::Unflagged)
+                .map_or(0, |idx| idx + 1);
+            if len > 0 {
+                let flags: String = flags[..len].iter().map(flag_to_char).collect();
+                writeln!(f, "page_flags {} {}", addr, flags)?;
+            }
+        }
+        if let Some(limit) = program.memory_limit {
+            writeln!(f, "memory_limit {}", limit)?;
+        }
+        writeln!(f, "executed {}", program.executed)?;
+        if let Some(addr) = program.halted {
+            writeln!(f, "halted {}", addr)?;
+        }
+        let policy = match program.code_policy {
+            CodePolicy::Allow => "allow",
+            CodePolicy::Warn => "warn",
+            CodePolicy::Fault => "fault",
+        };
+        writeln!(f, "code_policy {}", policy)?;
+        if let Some(run) = program.budget.run {
+            writeln!(f, "run_budget {}", run)?;
+        }
+        if let Some(total) = program.budget.total {
+            writeln!(f, "total_budget {}", total)?;
+        }
+        for watchpoint in program.watchpoints.iter() {
+            let access = match watchpoint.access {
+                Access::Read => "r",
+                Access::Write => "w",
+                Access::ReadWrite => "rw",
+            };
+            writeln!(
+                f,
+                "watch {} {} {}",
+                watchpoint.addrs.start, watchpoint.addrs.end, access
+            )?;
+        }
+        writeln!(f, "input {}", join(&self.input))
+    }
+}
+
+impl FromStr for Snapshot {
+    type Err = SnapshotError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+
+        let header = lines.next().unwrap_or("");
+        let version = match header.split_once(' ') {
+            Some((MAGIC, version)) => version
+                .trim()
+                .parse()
+                .map_err(|_| SnapshotError::Malformed(1, "version".to_string()))?,
+            _ => return Err(SnapshotError::NotASnapshot),
+        };
+        if version > VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let mut snapshot = Snapshot::default();
+        for (idx, line) in lines.enumerate() {
+            let line_nr = idx + 2;
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let malformed = || SnapshotError::Malformed(line_nr, key.to_string());
+
+            let program = &mut snapshot.program;
+            match key {
+                "instruction_pointer" => {
+                    program.instruction_pointer = value.parse().map_err(|_| malformed())?
+                }
+                "relative_base" => {
+                    program.relative_base = value.parse().map_err(|_| malformed())?
+                }
+                "memory" => program.memory = split(value).ok_or_else(malformed)?,
+                "memory_flags" => {
+                    program.memory_flags = value
+                        .chars()
+                        .map(char_to_flag)
+                        .collect::<Option<Vec<Flag>>>()
+                        .ok_or_else(malformed)?
+                }
+                "page" => {
+                    let (addr, values) = value.split_once(' ').unwrap_or((value, ""));
+                    let addr = addr.parse().map_err(|_| malformed())?;
+                    let values = split(values).ok_or_else(malformed)?;
+                    if !program.load_page(addr, &values) {
+                        return Err(SnapshotError::Page(line_nr, addr));
+                    }
+                }
+                "page_flags" => {
+                    let (addr, flags) = value.split_once(' ').unwrap_or((value, ""));
+                    let addr = addr.parse().map_err(|_| malformed())?;
+                    let flags = flags
+                        .chars()
+                        .map(char_to_flag)
+                        .collect::<Option<Vec<Flag>>>()
+                        .ok_or_else(malformed)?;
+                    if !program.load_page_flags(addr, &flags) {
+                        return Err(malformed());
+                    }
+                }
+                "memory_limit" => program.set_memory_limit(value.parse().map_err(|_| malformed())?),
+                "executed" => program.executed = value.parse().map_err(|_| malformed())?,
+                "halted" => program.halted = Some(value.parse().map_err(|_| malformed())?),
+                "code_policy" => {
+                    program.code_policy = match value {
+                        "allow" => CodePolicy::Allow,
+                        "warn" => CodePolicy::Warn,
+                        "fault" => CodePolicy::Fault,
+                        _ => return Err(malformed()),
+                    }
+                }
+                "run_budget" => program.budget.run = Some(value.parse().map_err(|_| malformed())?),
+                "total_budget" => {
+                    program.budget.total = Some(value.parse().map_err(|_| malformed())?)
+                }
+                "watch" => {
+                    let mut parts = value.split(' ');
+                    let mut addr = || -> Result<usize, SnapshotError> {
+                        parts.next().unwrap_or("").parse().map_err(|_| malformed())
+                    };
+                    let addrs = addr()?..addr()?;
+                    let access = match parts.next() {
+                        Some("r") => Access::Read,
+                        Some("w") => Access::Write,
+                        Some("rw") => Access::ReadWrite,
+                        _ => return Err(malformed()),
+                    };
+                    program.watchpoints.push(Watchpoint { addrs, access });
+                }
+                "input" => snapshot.input = split(value).ok_or_else(malformed)?,
+                _ => {} // Written by a newer version, ignore
+            }
+        }
+
+        let program = &mut snapshot.program;
+        if program.memory_flags.len() < program.memory.len() {
+            program
+                .memory_flags
+                .resize(program.memory.len(), Flag::Unflagged);
+        }
+
+        Ok(snapshot)
+    }
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    NotASnapshot,
+    UnsupportedVersion(u32),
+    // Line number and key of a value that could not be parsed
+    Malformed(usize, String),
+    // Line number and address of a page that is not aligned to PAGE_SIZE or too long
+    Page(usize, usize),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "{}", err),
+            SnapshotError::NotASnapshot => write!(f, "not an intcode snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::Malformed(line, key) => {
+                write!(f, "line {}: malformed value for {}", line, key)
+            }
+            SnapshotError::Page(line, addr) => {
+                write!(
+                    f,
+                    "line {}: page at {} is not aligned to {} cells or too long",
+                    line, addr, PAGE_SIZE
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self {
+        SnapshotError::Io(err)
+    }
+}
+
+fn join(values: &[i64]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn split(values: &str) -> Option<Vec<i64>> {
+    if values.trim().is_empty() {
+        return Some(Vec::new());
+    }
+    values
+        .split(',')
+        .map(|value| value.trim().parse().ok())
+        .collect()
+}
+
+fn flag_to_char(flag: &Flag) -> char {
+    match flag {
+        Flag::Unflagged => '.',
+        Flag::Inst => 'I',
+        Flag::Param => 'P',
+        Flag::ReadWrite => 'X',
+        Flag::Read => 'R',
+        Flag::Write => 'W',
+    }
+}
+
+fn char_to_flag(c: char) -> Option<Flag> {
+    match c {
+        '.' => Some(Flag::Unflagged),
+        'I' => Some(Flag::Inst),
+        'P' => Some(Flag::Param),
+        'X' => Some(Flag::ReadWrite),
+        'R' => Some(Flag::Read),
+        'W' => Some(Flag::Write),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::Interupt;
+
+    #[test]
+    fn round_trip() {
+        let mut program = Program::default();
+        program.reset(vec![1101, 3, 4, 1 << 30, 99]);
+        program.run(&mut Vec::new());
+
+        let snapshot: Snapshot = Snapshot::new(&program, &[7]).to_string().parse().unwrap();
+        assert_eq!(snapshot.input, [7]);
+        assert_eq!(snapshot.program.memory, program.memory);
+        assert_eq!(snapshot.program.peek(1 << 30), 7);
+        assert_eq!(
+            snapshot.to_string(),
+            Snapshot::new(&program, &[7]).to_string()
+        );
+    }
+
+    #[test]
+    fn budgets_continue() {
+        // Counts cell 20 down from 3 and halts
+        let mut program = Program::default();
+        program.reset(vec![1101, 3, 0, 20, 1001, 20, -1, 20, 1005, 20, 4, 99]);
+        program.set_code_policy(CodePolicy::Warn);
+        program.set_total_budget(5);
+        assert_eq!(program.run(&mut Vec::new()), Interupt::BudgetExhausted);
+
+        let mut restored: Snapshot = Snapshot::new(&program, &[]).to_string().parse().unwrap();
+        let program = &mut restored.program;
+        assert_eq!(program.executed(), 5);
+        assert_eq!(program.remaining(), Some(0));
+        assert_eq!(program.code_policy(), CodePolicy::Warn);
+        assert_eq!(program.run(&mut Vec::new()), Interupt::BudgetExhausted);
+
+        program.set_total_budget(100);
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Halt);
+        assert_eq!(program.executed(), 8);
+
+        let mut restored: Snapshot = Snapshot::new(program, &[]).to_string().parse().unwrap();
+        assert_eq!(restored.program.run(&mut Vec::new()), Interupt::Halt);
+        assert_eq!(restored.program.executed(), 8);
+    }
+
+    #[test]
+    fn page_flags_and_watchpoints() {
+        // Writes to a page, reads it back and writes to 14
+        let page = 1 << 30;
+        let mut program = Program::default();
+        program.reset(vec![
+            1101, 3, 4, page, 1001, page, 0, 13, 1101, 1, 1, 14, 99, 0, 0,
+        ]);
+        program.watch(page as usize..page as usize + 1, Access::Read);
+        program.watch(14..15, Access::Write);
+        assert!(matches!(
+            program.run(&mut Vec::new()),
+            Interupt::Watch { addr, .. } if addr == page as usize
+        ));
+
+        let text = Snapshot::new(&program, &[]).to_string();
+        assert!(text.contains(&format!("page_flags {} X\n", page)));
+        assert!(text.contains(&format!("watch {} {} r\n", page, page + 1)));
+        let mut restored: Snapshot = text.parse().unwrap();
+        let restored = &mut restored.program;
+        assert_eq!(restored.watchpoints, program.watchpoints);
+        assert_eq!(restored.pages().flags(), program.pages().flags());
+        assert_eq!(text, Snapshot::new(restored, &[]).to_string());
+
+        assert!(matches!(
+            restored.run(&mut Vec::new()),
+            Interupt::Watch {
+                addr: 14,
+                new: 2,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn unaligned_page() {
+        let snapshot = "intcode-snapshot 1\npage 1025 1,2\n".parse::<Snapshot>();
+        assert!(matches!(snapshot, Err(SnapshotError::Page(2, 1025))));
+    }
+}
@@ -0,0 +1,1095 @@
+use crate::error::{Fault, VmError};
+use crate::memory::DENSE_LIMIT;
+use crate::vm::{Inst, Opcode};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+// Symbolic execution of intcode programs.
+//
+// Memory cells and inputs can be replaced by symbols with a finite domain. Add and Multiply
+// build polynomials of the symbols, LessThan, Equals and the jumps fork the execution when
+// their outcome depends on a symbol. Every path ends with its constraints, outputs and memory
+// and Symbolic::solve hands them to a small solver for integer polynomial equations.
+//
+// A value read through a symbolic address is unknown. That is fine as long as it is
+// overwritten, like the first instruction of day 2, but a path that compares, outputs or
+// jumps on an unknown value is reported as unsupported.
+pub struct Symbolic {
+    memory: Vec<Value>,
+    inputs: Vec<Value>,
+    pub symbols: Vec<Symbol>,
+    // Instructions a single path may execute before exploring gives up
+    pub max_steps: usize,
+    pub max_paths: usize,
+    // Values the solver may try for one symbol or pair of symbols before giving up
+    pub max_values: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub domain: RangeInclusive<i64>,
+}
+
+// Product of symbols with their powers, sorted by symbol
+type Monomial = Vec<(usize, u32)>;
+
+// Polynomial with integer coefficients over symbols
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Poly {
+    terms: BTreeMap<Monomial, i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Const(i64),
+    Poly(Rc<Poly>),
+    // Read through a symbolic address
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rel {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+}
+
+// poly <rel> 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub poly: Poly,
+    pub rel: Rel,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum End {
+    Halt,
+    // The path needs more input than was given
+    Input,
+    Fault(Fault),
+}
+
+#[derive(Debug, Clone)]
+pub struct Path {
+    pub constraints: Vec<Constraint>,
+    pub outputs: Vec<Value>,
+    pub memory: Vec<Value>,
+    pub end: End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Output(usize),
+    LastOutput,
+    Memory(usize),
+}
+
+// Value per symbol, None if every value of its domain works
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Solution(pub Vec<Option<i64>>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolicError {
+    Unsupported { ip: usize, reason: &'static str },
+    StepLimit,
+    PathLimit,
+    ValueLimit,
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolicError::Unsupported { ip, reason } => {
+                write!(f, "unsupported at {}: {}", ip, reason)
+            }
+            SymbolicError::StepLimit => write!(f, "a path exceeded the step limit"),
+            SymbolicError::PathLimit => write!(f, "too many paths"),
+            SymbolicError::ValueLimit => write!(f, "too many values to try, narrow the domain"),
+        }
+    }
+}
+
+impl std::error::Error for SymbolicError {}
+
+// A coefficient overflowed while solving, the result would be wrong
+const OVERFLOW: SymbolicError = SymbolicError::Unsupported {
+    ip: 0,
+    reason: "coefficient overflow",
+};
+
+impl Poly {
+    pub fn constant(value: i64) -> Self {
+        let mut poly = Poly::default();
+        if value != 0 {
+            poly.terms.insert(Vec::new(), value);
+        }
+        poly
+    }
+    pub fn symbol(symbol: usize) -> Self {
+        let mut poly = Poly::default();
+        poly.terms.insert(vec![(symbol, 1)], 1);
+        poly
+    }
+    pub fn as_constant(&self) -> Option<i64> {
+        match self.terms.len() {
+            0 => Some(0),
+            1 => self.terms.get(&Vec::new()).copied(),
+            _ => None,
+        }
+    }
+    // Arithmetic returns None when a coefficient overflows
+    pub fn add(&self, other: &Poly) -> Option<Poly> {
+        let mut sum = self.clone();
+        for (monomial, coefficient) in other.terms.iter() {
+            sum.add_term(monomial.clone(), *coefficient)?;
+        }
+        Some(sum)
+    }
+    pub fn sub(&self, other: &Poly) -> Option<Poly> {
+        self.add(&other.scale(-1)?)
+    }
+    pub fn mul(&self, other: &Poly) -> Option<Poly> {
+        let mut product = Poly::default();
+        for (a, a_coefficient) in self.terms.iter() {
+            for (b, b_coefficient) in other.terms.iter() {
+                product.add_term(multiply(a, b), a_coefficient.checked_mul(*b_coefficient)?)?;
+            }
+        }
+        Some(product)
+    }
+    fn scale(&self, factor: i64) -> Option<Poly> {
+        let mut scaled = Poly::default();
+        for (monomial, coefficient) in self.terms.iter() {
+            scaled.add_term(monomial.clone(), coefficient.checked_mul(factor)?)?;
+        }
+        Some(scaled)
+    }
+    fn add_term(&mut self, monomial: Monomial, coefficient: i64) -> Option<()> {
+        let entry = self.terms.entry(monomial.clone()).or_insert(0);
+        *entry = entry.checked_add(coefficient)?;
+        if *entry == 0 {
+            self.terms.remove(&monomial);
+        }
+        Some(())
+    }
+    pub fn symbols(&self) -> BTreeSet<usize> {
+        self.terms
+            .keys()
+            .flat_map(|monomial| monomial.iter().map(|(symbol, _)| *symbol))
+            .collect()
+    }
+    // Highest power of symbol
+    pub fn degree_in(&self, symbol: usize) -> u32 {
+        self.terms
+            .keys()
+            .flat_map(|monomial| monomial.iter())
+            .filter(|(s, _)| *s == symbol)
+            .map(|(_, power)| *power)
+            .max()
+            .unwrap_or(0)
+    }
+    fn is_linear(&self) -> bool {
+        self.terms
+            .keys()
+            .all(|monomial| matches!(monomial.as_slice(), [] | [(_, 1)]))
+    }
+    fn coefficient(&self, monomial: &[(usize, u32)]) -> i64 {
+        self.terms.get(monomial).copied().unwrap_or(0)
+    }
+    // Replaces the assigned symbols by their value
+    pub fn substitute(&self, assignment: &[Option<i64>]) -> Option<Poly> {
+        let mut result = Poly::default();
+        for (monomial, coefficient) in self.terms.iter() {
+            let mut coefficient = *coefficient;
+            let mut rest = Vec::new();
+            for (symbol, power) in monomial.iter() {
+                match assignment.get(*symbol).copied().flatten() {
+                    Some(value) => {
+                        coefficient = coefficient.checked_mul(value.checked_pow(*power)?)?
+                    }
+                    None => rest.push((*symbol, *power)),
+                }
+            }
+            result.add_term(rest, coefficient)?;
+        }
+        Some(result)
+    }
+    // Lowest and highest value over the symbol domains
+    fn bounds(&self, symbols: &[Symbol]) -> (i128, i128) {
+        let mut bounds = (0i128, 0i128);
+        for (monomial, coefficient) in self.terms.iter() {
+            let mut term = (*coefficient as i128, *coefficient as i128);
+            for (symbol, power) in monomial.iter() {
+                let domain = &symbols[*symbol].domain;
+                let (lo, hi) = (*domain.start() as i128, *domain.end() as i128);
+                let (lo_pow, hi_pow) = (pow(lo, *power), pow(hi, *power));
+                let factor = if power % 2 == 0 && lo < 0 && hi > 0 {
+                    (0, lo_pow.max(hi_pow))
+                } else {
+                    (lo_pow.min(hi_pow), lo_pow.max(hi_pow))
+                };
+                term = interval_mul(term, factor);
+            }
+            bounds = (
+                bounds.0.saturating_add(term.0),
+                bounds.1.saturating_add(term.1),
+            );
+        }
+        bounds
+    }
+}
+
+impl fmt::Display for Poly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        // Highest degree first
+        for (idx, (monomial, coefficient)) in self.terms.iter().rev().enumerate() {
+            let sign = if *coefficient < 0 { "-" } else { "+" };
+            match idx {
+                0 if *coefficient < 0 => write!(f, "-")?,
+                0 => {}
+                _ => write!(f, " {} ", sign)?,
+            }
+            let abs = coefficient.abs();
+            if abs != 1 || monomial.is_empty() {
+                write!(f, "{}", abs)?;
+                if !monomial.is_empty() {
+                    write!(f, "*")?;
+                }
+            }
+            for (idx, (symbol, power)) in monomial.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, "*")?;
+                }
+                write!(f, "s{}", symbol)?;
+                if *power > 1 {
+                    write!(f, "^{}", power)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn multiply(a: &[(usize, u32)], b: &[(usize, u32)]) -> Monomial {
+    let mut powers = BTreeMap::new();
+    for (symbol, power) in a.iter().chain(b.iter()) {
+        *powers.entry(*symbol).or_insert(0) += power;
+    }
+    powers.into_iter().collect()
+}
+
+fn pow(value: i128, power: u32) -> i128 {
+    value.saturating_pow(power)
+}
+
+fn interval_mul(a: (i128, i128), b: (i128, i128)) -> (i128, i128) {
+    let products = [
+        a.0.saturating_mul(b.0),
+        a.0.saturating_mul(b.1),
+        a.1.saturating_mul(b.0),
+        a.1.saturating_mul(b.1),
+    ];
+    (
+        *products.iter().min().unwrap(),
+        *products.iter().max().unwrap(),
+    )
+}
+
+impl Value {
+    fn from_poly(poly: Poly) -> Value {
+        match poly.as_constant() {
+            Some(value) => Value::Const(value),
+            None => Value::Poly(Rc::new(poly)),
+        }
+    }
+    fn to_poly(&self) -> Option<Poly> {
+        match self {
+            Value::Const(value) => Some(Poly::constant(*value)),
+            Value::Poly(poly) => Some(poly.as_ref().clone()),
+            Value::Unknown => None,
+        }
+    }
+    // None on overflow
+    fn add(&self, other: &Value) -> Option<Value> {
+        match (self, other) {
+            (Value::Const(a), Value::Const(b)) => a.checked_add(*b).map(Value::Const),
+            (Value::Unknown, _) | (_, Value::Unknown) => Some(Value::Unknown),
+            _ => Some(Value::from_poly(self.to_poly()?.add(&other.to_poly()?)?)),
+        }
+    }
+    fn mul(&self, other: &Value) -> Option<Value> {
+        match (self, other) {
+            (Value::Const(a), Value::Const(b)) => a.checked_mul(*b).map(Value::Const),
+            (Value::Const(0), _) | (_, Value::Const(0)) => Some(Value::Const(0)),
+            (Value::Unknown, _) | (_, Value::Unknown) => Some(Value::Unknown),
+            _ => Some(Value::from_poly(self.to_poly()?.mul(&other.to_poly()?)?)),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Const(value) => write!(f, "{}", value),
+            Value::Poly(poly) => write!(f, "{}", poly),
+            Value::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+impl Constraint {
+    // Whether the constraint can hold somewhere in the symbol domains
+    fn feasible(&self, symbols: &[Symbol]) -> bool {
+        let (lo, hi) = self.poly.bounds(symbols);
+        match self.rel {
+            Rel::Eq => lo <= 0 && hi >= 0,
+            Rel::Ne => !(lo == 0 && hi == 0),
+            Rel::Lt => lo < 0,
+            Rel::Ge => hi >= 0,
+        }
+    }
+    fn holds(&self, value: i64) -> bool {
+        self.rel.holds(value)
+    }
+}
+
+impl Rel {
+    fn holds(self, value: i64) -> bool {
+        match self {
+            Rel::Eq => value == 0,
+            Rel::Ne => value != 0,
+            Rel::Lt => value < 0,
+            Rel::Ge => value >= 0,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct State {
+    memory: Vec<Value>,
+    instruction_pointer: usize,
+    relative_base: i64,
+    input: usize,
+    outputs: Vec<Value>,
+    constraints: Vec<Constraint>,
+    steps: usize,
+    // Set by a jump out of bounds, ends the path on the next step
+    fault: Option<Fault>,
+}
+
+enum Step {
+    Continue,
+    // The state took one outcome, the returned state the other
+    Fork(Box<State>),
+    End(End),
+}
+
+impl Symbolic {
+    pub fn new(image: &[i64]) -> Self {
+        Self {
+            memory: image.iter().map(|value| Value::Const(*value)).collect(),
+            inputs: Vec::new(),
+            symbols: Vec::new(),
+            max_steps: 1_000_000,
+            max_paths: 10_000,
+            max_values: 10_000_000,
+        }
+    }
+    fn symbol(&mut self, name: &str, domain: RangeInclusive<i64>) -> usize {
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            domain,
+        });
+        self.symbols.len() - 1
+    }
+    // Replaces the memory cell at addr by a new symbol and returns the symbol
+    pub fn symbol_memory(&mut self, addr: usize, name: &str, domain: RangeInclusive<i64>) -> usize {
+        let symbol = self.symbol(name, domain);
+        if addr >= self.memory.len() {
+            self.memory.resize(addr + 1, Value::Const(0));
+        }
+        self.memory[addr] = Value::Poly(Rc::new(Poly::symbol(symbol)));
+        symbol
+    }
+    // Adds a symbol to the input queue and returns the symbol
+    pub fn symbol_input(&mut self, name: &str, domain: RangeInclusive<i64>) -> usize {
+        let symbol = self.symbol(name, domain);
+        self.inputs.push(Value::Poly(Rc::new(Poly::symbol(symbol))));
+        symbol
+    }
+    pub fn input(&mut self, value: i64) {
+        self.inputs.push(Value::Const(value));
+    }
+    // Runs every feasible path to its end
+    pub fn explore(&self) -> Result<Vec<Path>, SymbolicError> {
+        let mut paths = Vec::new();
+        let mut states = vec![State {
+            memory: self.memory.clone(),
+            instruction_pointer: 0,
+            relative_base: 0,
+            input: 0,
+            outputs: Vec::new(),
+            constraints: Vec::new(),
+            steps: 0,
+            fault: None,
+        }];
+
+        while let Some(mut state) = states.pop() {
+            let end = loop {
+                if state.steps >= self.max_steps {
+                    return Err(SymbolicError::StepLimit);
+                }
+                state.steps += 1;
+
+                match self.step(&mut state)? {
+                    Step::Continue => {}
+                    Step::Fork(other) => {
+                        states.push(*other);
+                        if paths.len() + states.len() > self.max_paths {
+                            return Err(SymbolicError::PathLimit);
+                        }
+                    }
+                    Step::End(end) => break end,
+                }
+            };
+
+            paths.push(Path {
+                constraints: state.constraints,
+                outputs: state.outputs,
+                memory: state.memory,
+                end,
+            });
+        }
+
+        Ok(paths)
+    }
+    fn step(&self, state: &mut State) -> Result<Step, SymbolicError> {
+        if let Some(fault) = state.fault.take() {
+            return Ok(Step::End(End::Fault(fault)));
+        }
+        let ip = state.instruction_pointer;
+        let unsupported = |reason| Err(SymbolicError::Unsupported { ip, reason });
+        let cell =
+            |state: &State, addr: usize| state.memory.get(addr).cloned().unwrap_or(Value::Const(0));
+
+        let code = match cell(state, ip) {
+            Value::Const(code) => code,
+            _ => return unsupported("symbolic opcode"),
+        };
+        let fault = |error| {
+            Ok(Step::End(End::Fault(Fault {
+                error,
+                instruction_pointer: ip,
+                opcode: code,
+            })))
+        };
+        let opcode = match Opcode::from(code) {
+            Ok(opcode) => opcode,
+            Err(error) => return fault(error),
+        };
+        let modes = [opcode.a().mode, opcode.b().mode, opcode.c().mode];
+
+        // Address of operand idx, None if it is symbolic
+        let addr = |state: &State, idx: usize| -> Result<Option<i64>, VmError> {
+            let base = match modes[idx] {
+                0 => 0,
+                2 => state.relative_base,
+                mode => return Err(VmError::BadMode(mode)),
+            };
+            match cell(state, ip + 1 + idx) {
+                Value::Const(offset) => match base.checked_add(offset) {
+                    Some(addr) if addr < 0 => Err(VmError::NegativeAddress(addr)),
+                    Some(addr) => Ok(Some(addr)),
+                    None => Err(VmError::Overflow),
+                },
+                _ => Ok(None),
+            }
+        };
+        let read = |state: &State, idx: usize| -> Result<Value, VmError> {
+            if modes[idx] == 1 {
+                return Ok(cell(state, ip + 1 + idx));
+            }
+            Ok(match addr(state, idx)? {
+                Some(addr) => cell(state, addr as usize),
+                None => Value::Unknown,
+            })
+        };
+        let write = |state: &mut State, idx: usize, value: Value| -> Result<bool, VmError> {
+            if modes[idx] == 1 {
+                return Err(VmError::WriteInImmediateMode);
+            }
+            let Some(addr) = addr(state, idx)? else {
+                return Ok(false);
+            };
+            let addr = addr as usize;
+            // Symbolic memory is always dense
+            if addr >= DENSE_LIMIT {
+                return Err(VmError::MemoryLimit(addr as i64));
+            }
+            if addr >= state.memory.len() {
+                state.memory.resize(addr + 100, Value::Const(0));
+            }
+            state.memory[addr] = value;
+            Ok(true)
+        };
+
+        let params = opcode.inst.params();
+        let inputs = (0..params.min(2))
+            .map(|idx| read(state, idx))
+            .collect::<Result<Vec<Value>, VmError>>();
+        let inputs = match inputs {
+            Ok(inputs) => inputs,
+            Err(error) => return fault(error),
+        };
+
+        let next = ip + params + 1;
+        let result = match opcode.inst {
+            Inst::Add | Inst::Multiply => {
+                let value = if opcode.inst == Inst::Add {
+                    inputs[0].add(&inputs[1])
+                } else {
+                    inputs[0].mul(&inputs[1])
+                };
+                let Some(value) = value else {
+                    return unsupported("arithmetic overflow");
+                };
+                match write(state, 2, value) {
+                    Ok(true) => {}
+                    Ok(false) => return unsupported("write to a symbolic address"),
+                    Err(error) => return fault(error),
+                }
+                state.instruction_pointer = next;
+                Step::Continue
+            }
+            Inst::LessThan | Inst::Equals => {
+                let Some((a, b)) = inputs[0].to_poly().zip(inputs[1].to_poly()) else {
+                    return unsupported("comparison of an unknown value");
+                };
+                let Some(difference) = a.sub(&b) else {
+                    return unsupported("arithmetic overflow");
+                };
+                let (holds, fails) = match opcode.inst {
+                    Inst::LessThan => (Rel::Lt, Rel::Ge),
+                    _ => (Rel::Eq, Rel::Ne),
+                };
+
+                let mut other = None;
+                let value = match difference.as_constant() {
+                    Some(difference) => holds.holds(difference) as i64,
+                    None => match self.fork(state, difference, holds, fails) {
+                        (true, true) => {
+                            let mut fork = state.clone();
+                            fork.constraints.last_mut().unwrap().rel = fails;
+                            other = Some(fork);
+                            1
+                        }
+                        (true, false) => 1,
+                        (false, _) => 0,
+                    },
+                };
+
+                for (state, value) in [(Some(&mut *state), value), (other.as_mut(), 0)] {
+                    let Some(state) = state else { continue };
+                    match write(state, 2, Value::Const(value)) {
+                        Ok(true) => {}
+                        Ok(false) => return unsupported("write to a symbolic address"),
+                        Err(error) => return fault(error),
+                    }
+                    state.instruction_pointer = next;
+                }
+                match other {
+                    Some(other) => Step::Fork(Box::new(other)),
+                    None => Step::Continue,
+                }
+            }
+            Inst::JumpIfTrue | Inst::JumpIfFalse => {
+                let target = match &inputs[1] {
+                    Value::Const(target) => *target,
+                    _ => return unsupported("jump to a symbolic address"),
+                };
+                let jump_on_zero = opcode.inst == Inst::JumpIfFalse;
+
+                let mut other = None;
+                let zero = match &inputs[0] {
+                    Value::Const(condition) => *condition == 0,
+                    Value::Poly(condition) => {
+                        let condition = condition.as_ref().clone();
+                        match self.fork(state, condition, Rel::Eq, Rel::Ne) {
+                            (true, true) => {
+                                let mut fork = state.clone();
+                                fork.constraints.last_mut().unwrap().rel = Rel::Ne;
+                                other = Some(fork);
+                                true
+                            }
+                            (zero, _) => zero,
+                        }
+                    }
+                    Value::Unknown => return unsupported("jump on an unknown value"),
+                };
+
+                let jump = |state: &mut State, zero: bool| {
+                    if zero != jump_on_zero {
+                        state.instruction_pointer = next;
+                    } else if target < 0 || target as usize >= state.memory.len() {
+                        state.fault = Some(Fault {
+                            error: VmError::JumpOutOfBounds(target),
+                            instruction_pointer: ip,
+                            opcode: code,
+                        });
+                    } else {
+                        state.instruction_pointer = target as usize;
+                    }
+                };
+                jump(state, zero);
+                match other {
+                    Some(mut other) => {
+                        jump(&mut other, false);
+                        Step::Fork(Box::new(other))
+                    }
+                    None => Step::Continue,
+                }
+            }
+            Inst::Input => {
+                let Some(value) = self.inputs.get(state.input).cloned() else {
+                    return Ok(Step::End(End::Input));
+                };
+                match write(state, 0, value) {
+                    Ok(true) => {}
+                    Ok(false) => return unsupported("write to a symbolic address"),
+                    Err(error) => return fault(error),
+                }
+                state.input += 1;
+                state.instruction_pointer = next;
+                Step::Continue
+            }
+            Inst::Output => {
+                if inputs[0] == Value::Unknown {
+                    return unsupported("output of an unknown value");
+                }
+                state.outputs.push(inputs[0].clone());
+                state.instruction_pointer = next;
+                Step::Continue
+            }
+            Inst::AdjustRelativeBase => match inputs[0] {
+                Value::Const(value) => {
+                    let Some(relative_base) = state.relative_base.checked_add(value) else {
+                        return fault(VmError::Overflow);
+                    };
+                    state.relative_base = relative_base;
+                    state.instruction_pointer = next;
+                    Step::Continue
+                }
+                _ => return unsupported("symbolic relative base"),
+            },
+            Inst::Halt => Step::End(End::Halt),
+        };
+
+        Ok(result)
+    }
+    // Adds `poly <holds> 0` to the state if it is feasible, otherwise `poly <fails> 0`.
+    // Returns whether each of the two outcomes is feasible.
+    fn fork(&self, state: &mut State, poly: Poly, holds: Rel, fails: Rel) -> (bool, bool) {
+        let holds = Constraint {
+            poly: poly.clone(),
+            rel: holds,
+        };
+        let fails = Constraint { poly, rel: fails };
+        let outcomes = (holds.feasible(&self.symbols), fails.feasible(&self.symbols));
+        state
+            .constraints
+            .push(if outcomes.0 { holds } else { fails });
+        outcomes
+    }
+    // Finds every assignment of the symbols for which target equals value
+    pub fn solve(&self, target: Target, value: i64) -> Result<Vec<Solution>, SymbolicError> {
+        let mut solutions = BTreeSet::new();
+        for path in self.explore()? {
+            let result = match target {
+                Target::Output(idx) => path.outputs.get(idx),
+                Target::LastOutput => path.outputs.last(),
+                Target::Memory(addr) => path.memory.get(addr),
+            };
+            let poly = match result {
+                Some(Value::Unknown) => {
+                    return Err(SymbolicError::Unsupported {
+                        ip: 0,
+                        reason: "target read through a symbolic address",
+                    })
+                }
+                Some(result) => result.to_poly().unwrap(),
+                None if matches!(target, Target::Memory(_)) => Poly::default(),
+                None => continue,
+            };
+
+            let mut equations = vec![poly.sub(&Poly::constant(value)).ok_or(OVERFLOW)?];
+            let mut filters = Vec::new();
+            for constraint in path.constraints {
+                match constraint.rel {
+                    Rel::Eq => equations.push(constraint.poly),
+                    _ => filters.push(constraint),
+                }
+            }
+
+            let mut assignment = vec![None; self.symbols.len()];
+            self.solve_system(equations, filters, &mut assignment, &mut solutions)?;
+        }
+        Ok(solutions.into_iter().collect())
+    }
+    fn solve_system(
+        &self,
+        equations: Vec<Poly>,
+        filters: Vec<Constraint>,
+        assignment: &mut Vec<Option<i64>>,
+        solutions: &mut BTreeSet<Solution>,
+    ) -> Result<(), SymbolicError> {
+        let mut remaining = Vec::new();
+        for equation in equations {
+            let equation = equation.substitute(assignment).ok_or(OVERFLOW)?;
+            match equation.as_constant() {
+                Some(0) => {}
+                Some(_) => return Ok(()),
+                None => {
+                    let (lo, hi) = equation.bounds(&self.symbols);
+                    if lo > 0 || hi < 0 {
+                        return Ok(());
+                    }
+                    remaining.push(equation);
+                }
+            }
+        }
+        let mut remaining_filters = Vec::new();
+        for filter in filters {
+            let filter = Constraint {
+                poly: filter.poly.substitute(assignment).ok_or(OVERFLOW)?,
+                rel: filter.rel,
+            };
+            match filter.poly.as_constant() {
+                Some(value) if filter.holds(value) => {}
+                Some(_) => return Ok(()),
+                None if !filter.feasible(&self.symbols) => return Ok(()),
+                None => remaining_filters.push(filter),
+            }
+        }
+
+        let Some(equation) = remaining
+            .iter()
+            .min_by_key(|equation| equation.symbols().len())
+            .cloned()
+        else {
+            // Only inequalities left, try every value of their symbols
+            let symbols = remaining_filters
+                .iter()
+                .flat_map(|filter| filter.poly.symbols())
+                .collect::<BTreeSet<usize>>();
+            return match self.smallest_domain(&symbols) {
+                Some(symbol) => {
+                    self.branch(symbol, remaining, remaining_filters, assignment, solutions)
+                }
+                None => {
+                    solutions.insert(Solution(assignment.clone()));
+                    Ok(())
+                }
+            };
+        };
+
+        let symbols = equation.symbols().into_iter().collect::<Vec<usize>>();
+        let candidates: Vec<Vec<(usize, i64)>> = match symbols.as_slice() {
+            [symbol] => self
+                .roots(&equation, *symbol)?
+                .into_iter()
+                .map(|value| vec![(*symbol, value)])
+                .collect(),
+            [x, y] if equation.is_linear() => self.linear_pairs(&equation, *x, *y)?,
+            _ => {
+                // Enumerate a symbol, keeping one the equation is linear in for last
+                let linear = symbols
+                    .iter()
+                    .copied()
+                    .find(|symbol| equation.degree_in(*symbol) == 1);
+                let others = symbols
+                    .iter()
+                    .copied()
+                    .filter(|symbol| Some(*symbol) != linear)
+                    .collect::<BTreeSet<usize>>();
+                let symbol = self.smallest_domain(&others).unwrap();
+                return self.branch(symbol, remaining, remaining_filters, assignment, solutions);
+            }
+        };
+
+        for candidate in candidates {
+            for (symbol, value) in candidate.iter() {
+                assignment[*symbol] = Some(*value);
+            }
+            self.solve_system(
+                remaining.clone(),
+                remaining_filters.clone(),
+                assignment,
+                solutions,
+            )?;
+            for (symbol, _) in candidate.iter() {
+                assignment[*symbol] = None;
+            }
+        }
+        Ok(())
+    }
+    fn branch(
+        &self,
+        symbol: usize,
+        equations: Vec<Poly>,
+        filters: Vec<Constraint>,
+        assignment: &mut Vec<Option<i64>>,
+        solutions: &mut BTreeSet<Solution>,
+    ) -> Result<(), SymbolicError> {
+        let domain = &self.symbols[symbol].domain;
+        if domain.end().abs_diff(*domain.start()) >= self.max_values {
+            return Err(SymbolicError::ValueLimit);
+        }
+        for value in domain.clone() {
+            assignment[symbol] = Some(value);
+            self.solve_system(equations.clone(), filters.clone(), assignment, solutions)?;
+        }
+        assignment[symbol] = None;
+        Ok(())
+    }
+    fn smallest_domain(&self, symbols: &BTreeSet<usize>) -> Option<usize> {
+        symbols.iter().copied().min_by_key(|symbol| {
+            let domain = &self.symbols[*symbol].domain;
+            domain.end().saturating_sub(*domain.start())
+        })
+    }
+    // Values in the domain of symbol for which the single variable equation is zero
+    fn roots(&self, equation: &Poly, symbol: usize) -> Result<Vec<i64>, SymbolicError> {
+        let domain = &self.symbols[symbol].domain;
+        let evaluate = |value: i64| {
+            let mut assignment = vec![None; self.symbols.len()];
+            assignment[symbol] = Some(value);
+            let result = equation.substitute(&assignment).ok_or(OVERFLOW)?;
+            Ok(result.as_constant() == Some(0))
+        };
+
+        if equation.degree_in(symbol) == 1 {
+            // a*s + b = 0
+            let a = equation.coefficient(&[(symbol, 1)]);
+            let b = equation.coefficient(&[]);
+            let root = match b.checked_rem(a) {
+                Some(0) => b.checked_div(a).and_then(i64::checked_neg),
+                _ => None,
+            };
+            return Ok(root
+                .filter(|root| domain.contains(root))
+                .into_iter()
+                .collect());
+        }
+
+        // Integer roots divide the lowest order coefficient
+        let lowest = equation
+            .terms
+            .iter()
+            .next()
+            .map(|(_, coefficient)| coefficient.unsigned_abs())
+            .unwrap_or(0);
+        let size = domain.end().abs_diff(*domain.start());
+        let mut candidates = Vec::new();
+        if lowest == 0 || size < lowest.isqrt() * 2 {
+            if size >= self.max_values {
+                return Err(SymbolicError::ValueLimit);
+            }
+            candidates.extend(domain.clone());
+        } else {
+            if lowest.isqrt() > self.max_values {
+                return Err(SymbolicError::ValueLimit);
+            }
+            candidates.push(0);
+            let mut divisor = 1;
+            while divisor * divisor <= lowest {
+                if lowest.is_multiple_of(divisor) {
+                    for root in [divisor, lowest / divisor] {
+                        // -2^63 is a root candidate, 2^63 does not fit
+                        let root = root as i128;
+                        candidates.extend(i64::try_from(root).ok());
+                        candidates.extend(i64::try_from(-root).ok());
+                    }
+                }
+                divisor += 1;
+            }
+            candidates.sort();
+            candidates.dedup();
+        }
+
+        let mut roots = Vec::new();
+        for value in candidates {
+            if domain.contains(&value) && evaluate(value)? {
+                roots.push(value);
+            }
+        }
+        Ok(roots)
+    }
+    // Solutions of a*x + b*y + c = 0 within the domains of x and y
+    fn linear_pairs(
+        &self,
+        equation: &Poly,
+        x: usize,
+        y: usize,
+    ) -> Result<Vec<Vec<(usize, i64)>>, SymbolicError> {
+        let a = equation.coefficient(&[(x, 1)]) as i128;
+        let b = equation.coefficient(&[(y, 1)]) as i128;
+        let c = -(equation.coefficient(&[]) as i128);
+
+        let (g, s, t) = extended_gcd(a, b);
+        if c % g != 0 {
+            return Ok(Vec::new());
+        }
+        // x = x0 + k*dx, y = y0 - k*dy
+        let (x0, y0) = (s * (c / g), t * (c / g));
+        let (dx, dy) = (b / g, a / g);
+
+        let x_domain = &self.symbols[x].domain;
+        let y_domain = &self.symbols[y].domain;
+        let (k_lo, k_hi) = intersect(
+            k_range(x0, dx, *x_domain.start() as i128, *x_domain.end() as i128),
+            k_range(y0, -dy, *y_domain.start() as i128, *y_domain.end() as i128),
+        );
+
+        if k_hi.saturating_sub(k_lo) >= self.max_values as i128 {
+            return Err(SymbolicError::ValueLimit);
+        }
+        Ok((k_lo..=k_hi)
+            .map(|k| vec![(x, (x0 + k * dx) as i64), (y, (y0 - k * dy) as i64)])
+            .collect())
+    }
+}
+
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        return (a.abs(), a.signum(), 0);
+    }
+    let (g, s, t) = extended_gcd(b, a % b);
+    (g, t, s - (a / b) * t)
+}
+
+// Range of k for which lo <= start + k*step <= hi
+fn k_range(start: i128, step: i128, lo: i128, hi: i128) -> (i128, i128) {
+    if step == 0 {
+        return if (lo..=hi).contains(&start) {
+            (i128::MIN / 4, i128::MAX / 4)
+        } else {
+            (1, 0)
+        };
+    }
+    let (a, b) = ((lo - start), (hi - start));
+    if step > 0 {
+        (
+            a.div_euclid(step) + (a.rem_euclid(step) != 0) as i128,
+            b.div_euclid(step),
+        )
+    } else {
+        let step = -step;
+        (
+            (-b).div_euclid(step) + ((-b).rem_euclid(step) != 0) as i128,
+            (-a).div_euclid(step),
+        )
+    }
+}
+
+fn intersect(a: (i128, i128), b: (i128, i128)) -> (i128, i128) {
+    (a.0.max(b.0), a.1.min(b.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // memory[0] = 100 * noun + verb, with the noun and verb at 1 and 2
+    const NOUN_VERB: [i64; 13] = [1101, 0, 0, 3, 1002, 1, 100, 0, 1, 0, 2, 0, 99];
+
+    #[test]
+    fn solve_noun_verb() {
+        let mut symbolic = Symbolic::new(&NOUN_VERB);
+        symbolic.symbol_memory(1, "noun", 0..=99);
+        symbolic.symbol_memory(2, "verb", 0..=99);
+
+        let solutions = symbolic.solve(Target::Memory(0), 1234).unwrap();
+        assert_eq!(solutions, [Solution(vec![Some(12), Some(34)])]);
+        assert!(symbolic
+            .solve(Target::Memory(0), 10_000)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn overflow_is_unsupported() {
+        // memory[0] = (noun * 2^62)^2
+        let memory = [1101, 0, 0, 3, 1002, 1, 1 << 62, 0, 2, 0, 0, 0, 99];
+        let mut symbolic = Symbolic::new(&memory);
+        symbolic.symbol_memory(1, "noun", 0..=99);
+
+        assert_eq!(
+            symbolic.solve(Target::Memory(0), 0),
+            Err(SymbolicError::Unsupported {
+                ip: 8,
+                reason: "arithmetic overflow"
+            })
+        );
+    }
+
+    #[test]
+    fn fork_path_limit() {
+        // Compares the symbol at 7 forever, every comparison forks without ending the path
+        let memory = [1007, 7, 10, 8, 1105, 1, 0, 0, 0];
+        let mut symbolic = Symbolic::new(&memory);
+        symbolic.symbol_memory(7, "a", 0..=99);
+        symbolic.max_paths = 100;
+        assert_eq!(symbolic.explore().unwrap_err(), SymbolicError::PathLimit);
+    }
+
+    #[test]
+    fn lowest_coefficient_min() {
+        // s^2 + i64::MIN = 0 has no integer root, with -2^63 as a divisor candidate
+        let mut symbolic = Symbolic::new(&[]);
+        symbolic.symbol_input("s", i64::MIN..=i64::MAX);
+        let square = Poly::symbol(0).mul(&Poly::symbol(0)).unwrap();
+        let equation = square.add(&Poly::constant(i64::MIN)).unwrap();
+        assert_eq!(symbolic.roots(&equation, 0), Err(SymbolicError::ValueLimit));
+
+        symbolic.max_values = u64::MAX;
+        let equation = square.add(&Poly::constant(-(1 << 20))).unwrap();
+        assert_eq!(symbolic.roots(&equation, 0), Ok(vec![-(1 << 10), 1 << 10]));
+    }
+
+    #[test]
+    fn huge_domain() {
+        let mut symbolic = Symbolic::new(&NOUN_VERB);
+        symbolic.symbol_memory(1, "noun", i64::MIN..=i64::MAX);
+        symbolic.symbol_memory(2, "verb", i64::MIN..=i64::MAX);
+        assert_eq!(
+            symbolic.solve(Target::Memory(0), 1234),
+            Err(SymbolicError::ValueLimit)
+        );
+
+        // A relative base offset that overflows faults the path instead of panicking
+        let memory = [109, i64::MAX, 1201, 1, 0, 10, 99];
+        let paths = Symbolic::new(&memory).explore().unwrap();
+        assert!(matches!(
+            &paths[0].end,
+            End::Fault(fault) if fault.error == VmError::Overflow
+        ));
+    }
+
+    #[test]
+    fn checked_poly() {
+        let max = Poly::constant(i64::MAX);
+        assert_eq!(max.add(&Poly::constant(1)), None);
+        assert_eq!(max.sub(&Poly::constant(-1)), None);
+
+        let poly = Poly::symbol(0).mul(&Poly::symbol(0)).unwrap();
+        assert_eq!(poly.substitute(&[Some(3)]).unwrap().as_constant(), Some(9));
+        assert_eq!(poly.substitute(&[Some(1 << 32)]), None);
+    }
+}
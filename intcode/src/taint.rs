@@ -0,0 +1,227 @@
+use crate::disasm::{Instruction, Operand};
+use crate::error::VmError;
+use crate::vm::{InputCursor, Inst, Interupt, Program};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+// Where a value came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    // Index of the consumed input value, counted from the last reset
+    Input(usize),
+    // Memory cell marked with Program::taint_memory
+    Memory(usize),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Input(idx) => write!(f, "input {}", idx),
+            Source::Memory(addr) => write!(f, "memory {}", addr),
+        }
+    }
+}
+
+pub type Tags = BTreeSet<Source>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaintedOutput {
+    pub value: i64,
+    // Sources the output value was computed from
+    pub data: Tags,
+    // Sources of every jump decision and relative base change made before the output
+    pub control: Tags,
+}
+
+// Shadow memory that tags every value with the sources it was derived from, only recorded
+// while enabled on a Program. Instructions undone with the journal are not undone here.
+#[derive(Clone, Default)]
+pub struct Taint {
+    // Only cells with tags, memory can be sparse
+    shadow: HashMap<usize, Tags>,
+    relative_base: Tags,
+    control: Tags,
+    inputs: usize,
+    pub outputs: Vec<TaintedOutput>,
+}
+
+impl Taint {
+    // Sources of the value at addr
+    pub fn tags(&self, addr: usize) -> Tags {
+        self.shadow.get(&addr).cloned().unwrap_or_default()
+    }
+    // Tags of an operand value, which also depends on the memory cell holding the operand
+    fn operand(&self, cell: usize, operand: Operand, relative_base: i64) -> Tags {
+        let mut tags = self.tags(cell);
+        match operand {
+            Operand::Immediate(_) => {}
+            Operand::Position(addr) if addr < 0 => {}
+            Operand::Position(addr) => tags.extend(self.tags(addr as usize)),
+            Operand::Relative(offset) => {
+                tags.extend(self.relative_base.iter().copied());
+                // Jump targets are not resolved when the jump is not taken, so this can overflow
+                match relative_base.checked_add(offset) {
+                    Some(addr) if addr >= 0 => tags.extend(self.tags(addr as usize)),
+                    _ => {}
+                }
+            }
+        }
+        tags
+    }
+    fn set(&mut self, cell: usize, operand: Operand, relative_base: i64, mut tags: Tags) {
+        // Which cell is written depends on the operand cell and the relative base
+        tags.extend(self.tags(cell));
+        let addr = match operand {
+            Operand::Position(addr) => addr,
+            Operand::Relative(offset) => {
+                tags.extend(self.relative_base.iter().copied());
+                match relative_base.checked_add(offset) {
+                    Some(addr) => addr,
+                    None => return,
+                }
+            }
+            Operand::Immediate(_) => return,
+        };
+        if tags.is_empty() {
+            self.shadow.remove(&(addr as usize));
+        } else {
+            self.shadow.insert(addr as usize, tags);
+        }
+    }
+    fn apply(&mut self, addr: usize, inst: &Instruction, relative_base: i64, output: Option<i64>) {
+        let ops = &inst.operands;
+        let tags = |idx: usize| self.operand(addr + 1 + idx, ops[idx], relative_base);
+
+        match inst.inst {
+            Inst::Add | Inst::Multiply | Inst::LessThan | Inst::Equals => {
+                let mut value = tags(0);
+                value.extend(tags(1));
+                self.set(addr + 3, ops[2], relative_base, value);
+            }
+            Inst::Input => {
+                let source = Source::Input(self.inputs);
+                self.inputs += 1;
+                self.set(addr + 1, ops[0], relative_base, Tags::from([source]));
+            }
+            Inst::Output => {
+                let data = tags(0);
+                self.outputs.push(TaintedOutput {
+                    value: output.unwrap_or_default(),
+                    data,
+                    control: self.control.clone(),
+                });
+            }
+            Inst::JumpIfTrue | Inst::JumpIfFalse => {
+                let (condition, target) = (tags(0), tags(1));
+                self.control.extend(condition);
+                self.control.extend(target);
+            }
+            Inst::AdjustRelativeBase => {
+                let value = tags(0);
+                self.control.extend(value.iter().copied());
+                self.relative_base.extend(value);
+            }
+            Inst::Halt => {}
+        }
+    }
+}
+
+impl fmt::Display for Taint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |tags: &Tags| {
+            if tags.is_empty() {
+                return "-".to_string();
+            }
+            tags.iter()
+                .map(|source| source.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        for (idx, output) in self.outputs.iter().enumerate() {
+            writeln!(
+                f,
+                "output {} ({}): data: {} control: {}",
+                idx,
+                output.value,
+                join(&output.data),
+                join(&output.control)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Program {
+    pub fn enable_taint(&mut self) {
+        if self.taint.is_none() {
+            self.taint = Some(Taint::default());
+        }
+    }
+    pub fn disable_taint(&mut self) {
+        self.taint = None;
+    }
+    pub fn taint(&self) -> Option<&Taint> {
+        self.taint.as_ref()
+    }
+    // Marks addr as a source, for programs that take their input from patched memory
+    pub fn taint_memory(&mut self, addr: usize) {
+        if let Some(taint) = &mut self.taint {
+            taint
+                .shadow
+                .insert(addr, Tags::from([Source::Memory(addr)]));
+        }
+    }
+    // Program::exec_traced that also tracks taint if enabled
+    pub(crate) fn exec_tainted(
+        &mut self,
+        input: &mut InputCursor,
+    ) -> Result<Option<Interupt>, VmError> {
+        if self.taint.is_none() {
+            return self.exec_traced(input);
+        }
+
+        // Operands are resolved with the state from before the instruction ran
+        let instruction_pointer = self.instruction_pointer;
+        let inst = self.instruction_at(instruction_pointer);
+        let relative_base = self.relative_base;
+
+        let result = self.exec_traced(input);
+        let output = match result {
+            Ok(Some(Interupt::Output(value))) => Some(value),
+            Ok(None | Some(Interupt::Halt)) => None,
+            _ => return result,
+        };
+        if let (Some(inst), Some(taint)) = (inst, &mut self.taint) {
+            taint.apply(instruction_pointer, &inst, relative_base, output);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sparse_write() {
+        let mut program = Program::default();
+        program.enable_taint();
+        program.reset(vec![3, 1_000_000_000_000, 4, 1_000_000_000_000, 99]);
+
+        assert_eq!(program.run(&mut vec![5]), Interupt::Output(5));
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Halt);
+        let output = &program.taint().unwrap().outputs[0];
+        assert_eq!(output.data, Tags::from([Source::Input(0)]));
+    }
+
+    #[test]
+    fn untaken_jump_overflow() {
+        let mut program = Program::default();
+        program.enable_taint();
+        // The relative target of the jump is never resolved because the jump is not taken
+        program.reset(vec![109, 1, 2206, 1, i64::MAX, 99]);
+
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Halt);
+    }
+}
@@ -0,0 +1,498 @@
+use crate::disasm::Operand;
+use crate::error::VmError;
+use crate::vm::{InputCursor, Interupt, Program};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+// One executed instruction, written as a line of JSON:
+//
+//     {"step":3,"ip":4,"rb":0,"inst":"MUL [3], #3 -> [0]","operands":[70,3],"write":[0,210]}
+//
+// operands are the resolved values of the operands the instruction reads and write is the
+// address and value it wrote, or null. The format is simple enough for other intcode
+// implementations to produce, so their traces can be compared with this one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    pub step: u64,
+    pub ip: usize,
+    pub relative_base: i64,
+    pub inst: String,
+    pub operands: Vec<i64>,
+    pub write: Option<(usize, i64)>,
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operands = self
+            .operands
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        let write = match self.write {
+            Some((addr, value)) => format!("[{},{}]", addr, value),
+            None => "null".to_string(),
+        };
+        write!(
+            f,
+            "{{\"step\":{},\"ip\":{},\"rb\":{},\"inst\":\"{}\",\"operands\":[{}],\"write\":{}}}",
+            self.step,
+            self.ip,
+            self.relative_base,
+            self.inst.replace('\\', "\\\\").replace('"', "\\\""),
+            operands,
+            write
+        )
+    }
+}
+
+impl FromStr for TraceStep {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { rest: s.trim() };
+        parser.expect("{")?;
+        let mut step = TraceStep {
+            step: 0,
+            ip: 0,
+            relative_base: 0,
+            inst: String::new(),
+            operands: Vec::new(),
+            write: None,
+        };
+        loop {
+            let key = parser.string()?;
+            parser.expect(":")?;
+            match key.as_str() {
+                "step" => step.step = parser.number()? as u64,
+                "ip" => step.ip = parser.number()? as usize,
+                "rb" => step.relative_base = parser.number()?,
+                "inst" => step.inst = parser.string()?,
+                "operands" => step.operands = parser.numbers()?,
+                "write" if parser.eat("null") => step.write = None,
+                "write" => match parser.numbers()?.as_slice() {
+                    [addr, value] => step.write = Some((*addr as usize, *value)),
+                    _ => return Err("write must be [addr, value]".to_string()),
+                },
+                key => return Err(format!("unknown key {}", key)),
+            }
+            if !parser.eat(",") {
+                break;
+            }
+        }
+        parser.expect("}")?;
+        Ok(step)
+    }
+}
+
+// Just enough JSON for a TraceStep
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    fn eat(&mut self, token: &str) -> bool {
+        self.rest = self.rest.trim_start();
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        match self.eat(token) {
+            true => Ok(()),
+            false => Err(format!("expected {}", token)),
+        }
+    }
+    fn number(&mut self) -> Result<i64, String> {
+        self.rest = self.rest.trim_start();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+            .unwrap_or(self.rest.len());
+        let number = self.rest[..end]
+            .parse()
+            .map_err(|_| "expected a number".to_string())?;
+        self.rest = &self.rest[end..];
+        Ok(number)
+    }
+    fn numbers(&mut self) -> Result<Vec<i64>, String> {
+        self.expect("[")?;
+        let mut numbers = Vec::new();
+        if self.eat("]") {
+            return Ok(numbers);
+        }
+        loop {
+            numbers.push(self.number()?);
+            if !self.eat(",") {
+                break;
+            }
+        }
+        self.expect("]")?;
+        Ok(numbers)
+    }
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut string = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[idx + 1..];
+                    return Ok(string);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c)) => string.push(c),
+                    None => break,
+                },
+                c => string.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+}
+
+// Streams every executed instruction to a writer, only recorded while enabled on a Program.
+// Clones of the program write to the same writer and continue the same step count.
+#[derive(Clone)]
+pub struct Tracer {
+    out: Arc<Mutex<dyn Write + Send>>,
+    steps: Arc<AtomicU64>,
+    // First write error, tracing stops after it
+    error: Arc<Mutex<Option<io::Error>>>,
+}
+
+impl Tracer {
+    fn record(&mut self, step: TraceStep) {
+        let mut error = self.error.lock().unwrap();
+        if error.is_some() {
+            return;
+        }
+        if let Err(err) = writeln!(self.out.lock().unwrap(), "{}", step) {
+            *error = Some(err);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    // The first step where the traces differ, with the names of the fields that differ
+    Step {
+        line: usize,
+        fields: Vec<&'static str>,
+        left: Box<TraceStep>,
+        right: Box<TraceStep>,
+    },
+    // One trace ends while the other goes on, left is true if the left trace is longer
+    Length {
+        line: usize,
+        left: bool,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Divergence::Step {
+                line,
+                fields,
+                left,
+                right,
+            } => {
+                writeln!(f, "line {}: different {}", line, fields.join(", "))?;
+                writeln!(f, "< {}", left)?;
+                write!(f, "> {}", right)
+            }
+            Divergence::Length { line, left: true } => {
+                write!(f, "line {}: the right trace ends here", line)
+            }
+            Divergence::Length { line, left: false } => {
+                write!(f, "line {}: the left trace ends here", line)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    // Line number and what is wrong with it
+    Malformed(usize, String),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraceError::Io(err) => write!(f, "{}", err),
+            TraceError::Malformed(line, reason) => write!(f, "line {}: {}", line, reason),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl From<io::Error> for TraceError {
+    fn from(err: io::Error) -> Self {
+        TraceError::Io(err)
+    }
+}
+
+// Compares two JSONL traces step by step, None if they are equal
+pub fn diff(left: impl BufRead, right: impl BufRead) -> Result<Option<Divergence>, TraceError> {
+    let parse = |line: usize, text: io::Result<String>| -> Result<TraceStep, TraceError> {
+        text?
+            .parse()
+            .map_err(|reason| TraceError::Malformed(line, reason))
+    };
+
+    let mut left = left.lines();
+    let mut right = right.lines();
+    for line in 1.. {
+        let (a, b) = match (left.next(), right.next()) {
+            (None, None) => return Ok(None),
+            (Some(_), None) => return Ok(Some(Divergence::Length { line, left: true })),
+            (None, Some(_)) => return Ok(Some(Divergence::Length { line, left: false })),
+            (Some(a), Some(b)) => (parse(line, a)?, parse(line, b)?),
+        };
+
+        let mut fields = Vec::new();
+        if a.ip != b.ip {
+            fields.push("ip");
+        }
+        if a.relative_base != b.relative_base {
+            fields.push("rb");
+        }
+        if a.inst != b.inst {
+            fields.push("inst");
+        }
+        if a.operands != b.operands {
+            fields.push("operands");
+        }
+        if a.write != b.write {
+            fields.push("write");
+        }
+        // The step counter alone differs when one trace started later
+        if !fields.is_empty() {
+            return Ok(Some(Divergence::Step {
+                line,
+                fields,
+                left: Box::new(a),
+                right: Box::new(b),
+            }));
+        }
+    }
+    unreachable!()
+}
+
+impl Program {
+    // Streams a TraceStep per executed instruction to out, as JSON lines
+    pub fn enable_trace(&mut self, out: impl Write + Send + 'static) {
+        self.trace = Some(Tracer {
+            out: Arc::new(Mutex::new(out)),
+            steps: Arc::new(AtomicU64::new(0)),
+            error: Arc::new(Mutex::new(None)),
+        });
+    }
+    // Stops tracing, flushes the writer and returns the first error writing the trace
+    pub fn finish_trace(&mut self) -> io::Result<()> {
+        let Some(trace) = self.trace.take() else {
+            return Ok(());
+        };
+        if let Some(err) = trace.error.lock().unwrap().take() {
+            return Err(err);
+        }
+        let result = trace.out.lock().unwrap().flush();
+        result
+    }
+    // Value an operand resolves to with the current state. An address that overflows
+    // resolves to 0, the instruction faults on it and is not traced.
+    fn resolve(&self, operand: Operand) -> i64 {
+        let addr = match operand {
+            Operand::Immediate(value) => return value,
+            Operand::Position(addr) => addr,
+            Operand::Relative(offset) => match self.relative_base.checked_add(offset) {
+                Some(addr) => addr,
+                None => return 0,
+            },
+        };
+        self.peek(addr.max(0) as usize)
+    }
+    // Program::exec that also traces the instruction if enabled
+    pub(crate) fn exec_traced(
+        &mut self,
+        input: &mut InputCursor,
+    ) -> Result<Option<Interupt>, VmError> {
+        if self.trace.is_none() {
+            return self.exec(input);
+        }
+
+        let ip = self.instruction_pointer;
+        let inst = self.instruction_at(ip);
+        let relative_base = self.relative_base;
+        let (operands, dest) = match &inst {
+            Some(inst) => {
+                let output = inst.inst.output_param();
+                let operands = inst
+                    .operands
+                    .iter()
+                    .enumerate()
+                    .filter(|(idx, _)| Some(*idx) != output)
+                    .map(|(_, operand)| self.resolve(*operand))
+                    .collect();
+                let dest = output.and_then(|idx| match inst.operands[idx] {
+                    Operand::Position(addr) => Some(addr),
+                    Operand::Relative(offset) => relative_base.checked_add(offset),
+                    Operand::Immediate(_) => None,
+                });
+                (operands, dest)
+            }
+            None => (Vec::new(), None),
+        };
+
+        let result = self.exec(input);
+        let (Ok(None | Some(Interupt::Output(_) | Interupt::Halt)), Some(inst)) = (&result, inst)
+        else {
+            return result;
+        };
+
+        let write = dest.map(|addr| (addr as usize, self.peek(addr as usize)));
+        if let Some(trace) = &mut self.trace {
+            let step = TraceStep {
+                step: trace.steps.fetch_add(1, Ordering::Relaxed),
+                ip,
+                relative_base,
+                inst: inst.to_string(),
+                operands,
+                write,
+            };
+            trace.record(step);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trace output that stays readable after it was handed to the program
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        fn steps(&self) -> Vec<TraceStep> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| line.parse().unwrap())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let step = TraceStep {
+            step: 3,
+            ip: 4,
+            relative_base: -2,
+            inst: "MUL [3], #3 -> [0] \\ \"".to_string(),
+            operands: vec![70, -3],
+            write: Some((0, 210)),
+        };
+        assert_eq!(step.to_string().parse::<TraceStep>(), Ok(step.clone()));
+
+        let step = TraceStep {
+            operands: Vec::new(),
+            write: None,
+            ..step
+        };
+        assert_eq!(step.to_string().parse::<TraceStep>(), Ok(step));
+        assert!("{\"step\":1".parse::<TraceStep>().is_err());
+    }
+
+    #[test]
+    fn halt_traced_once() {
+        let out = Shared::default();
+        let mut program = Program::default();
+        program.enable_trace(out.clone());
+        program.reset(vec![1101, 1, 2, 5, 99, 0]);
+        for _ in 0..3 {
+            assert_eq!(program.run(&mut Vec::new()), Interupt::Halt);
+        }
+
+        let steps = out.steps();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].write, Some((5, 3)));
+        assert_eq!(steps[1].inst, "HLT");
+    }
+
+    #[test]
+    fn clones_share_steps() {
+        let out = Shared::default();
+        let mut program = Program::default();
+        program.enable_trace(out.clone());
+        program.reset(vec![104, 1, 104, 2, 99]);
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Output(1));
+
+        let mut clone = program.clone();
+        assert_eq!(clone.run(&mut Vec::new()), Interupt::Output(2));
+        assert_eq!(program.run(&mut Vec::new()), Interupt::Output(2));
+        let steps = out
+            .steps()
+            .iter()
+            .map(|step| step.step)
+            .collect::<Vec<u64>>();
+        assert_eq!(steps, [0, 1, 2]);
+    }
+
+    #[test]
+    fn diff_traces() {
+        let trace = |values: &[i64]| {
+            let out = Shared::default();
+            let mut program = Program::default();
+            program.enable_trace(out.clone());
+            program.reset(vec![3, 9, 4, 9, 3, 9, 4, 9, 99, 0]);
+            let mut input = values.to_vec();
+            while let Interupt::Output(_) = program.run(&mut input) {}
+            let bytes = out.0.lock().unwrap().clone();
+            bytes
+        };
+
+        let left = trace(&[1, 2]);
+        assert_eq!(diff(&left[..], &left[..]).unwrap(), None);
+
+        let Some(Divergence::Step { line, fields, .. }) =
+            diff(&left[..], &trace(&[1, 3])[..]).unwrap()
+        else {
+            panic!("expected a step divergence");
+        };
+        assert_eq!((line, fields), (3, vec!["write"]));
+
+        assert_eq!(
+            diff(&left[..], &trace(&[1])[..]).unwrap(),
+            Some(Divergence::Length {
+                line: 3,
+                left: true
+            })
+        );
+        assert!(matches!(
+            diff(&b"{}\n"[..], &left[..]),
+            Err(TraceError::Malformed(1, _))
+        ));
+    }
+}
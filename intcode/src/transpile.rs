@@ -0,0 +1,298 @@
+use crate::disasm::{self, Instruction, Operand};
+use crate::vm::Inst;
+use std::fmt::Write;
+
+// Compiles a memory image into Rust source for a struct with the same
+// `run(&mut input) -> Interupt` interface as Program.
+//
+// Every basic block found by disasm::discover becomes an arm of a `match` on the instruction
+// pointer.
+// The generated code hands control to the wrapped Program for good when it reaches anything
+// it was not compiled for: a write into a code cell or past the memory limit, a jump to an
+// unknown address, a negative address or an undecodable instruction. The interpreter then
+// continues from the same state, so faults are reported exactly like Program::run does.
+// The compiled code ignores the Program features listed in IGNORED, the generated file repeats
+// them.
+pub const IGNORED: &str = "\
+The compiled code does not count executed instructions or enforce instruction budgets,
+does not check watchpoints and does not update memory_flags, the journal, profile, taint,
+trace or session of the wrapped Program. All of them work again once the interpreter takes
+over after a fallback.";
+
+pub fn transpile(memory: &[i64], name: &str) -> String {
+    let (instructions, leaders) = disasm::discover(memory);
+
+    let mut code = vec![false; memory.len()];
+    for (addr, inst) in instructions.iter() {
+        let end = (*addr + inst.size()).min(memory.len());
+        code[*addr..end].fill(true);
+    }
+
+    let mut out = String::new();
+    writeln!(out, "// Generated by intcode::transpile, do not edit").unwrap();
+    writeln!(out, "//").unwrap();
+    for line in IGNORED.lines() {
+        writeln!(out, "// {}", line).unwrap();
+    }
+    writeln!(out, "#![allow(clippy::all, unused)]").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "use intcode::{{Interupt, Program}};").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "const MEMORY: [i64; {}] = {:?};", memory.len(), memory).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "// Cells that belong to compiled instructions").unwrap();
+    writeln!(out, "const CODE: [bool; {}] = {:?};", code.len(), code).unwrap();
+    writeln!(out).unwrap();
+    write!(
+        out,
+        "pub struct {name} {{
+    pub program: Program,
+    interpreted: bool,
+}}
+
+impl Default for {name} {{
+    fn default() -> Self {{
+        let mut program = Program::default();
+        program.reset(MEMORY.to_vec());
+        Self {{
+            program,
+            interpreted: false,
+        }}
+    }}
+}}
+
+impl {name} {{
+    pub fn run(&mut self, input: &mut Vec<i64>) -> Interupt {{
+        if !self.interpreted {{
+            // Like Program::run, read input is removed once when the compiled code returns
+            let mut consumed = 0;
+            let interupt = self.run_compiled(input, &mut consumed);
+            input.drain(..consumed);
+            if let Some(interupt) = interupt {{
+                return interupt;
+            }}
+        }}
+        self.program.run(input)
+    }}
+    // Returns None when the interpreter has to take over
+    fn run_compiled(&mut self, input: &[i64], consumed: &mut usize) -> Option<Interupt> {{
+        let mut ip = self.program.instruction_pointer;
+        loop {{
+            match ip {{
+"
+    )
+    .unwrap();
+
+    for leader in leaders.iter() {
+        writeln!(out, "                {} => {{", leader).unwrap();
+        let mut addr = *leader;
+        loop {
+            let Some(inst) = instructions.get(&addr) else {
+                // Not reachable by static analysis, let the interpreter decide
+                writeln!(out, "                    return self.fallback({});", addr).unwrap();
+                break;
+            };
+
+            writeln!(out, "                    // {:04}: {}", addr, inst).unwrap();
+            let ends_block = emit(&mut out, addr, inst);
+            addr += inst.size();
+
+            if ends_block {
+                break;
+            }
+            if leaders.contains(&addr) {
+                writeln!(out, "                    ip = {};", addr).unwrap();
+                writeln!(out, "                    continue;").unwrap();
+                break;
+            }
+        }
+        writeln!(out, "                }}").unwrap();
+    }
+
+    write!(
+        out,
+        "                _ => return self.fallback(ip),
+            }}
+        }}
+    }}
+    // Continue in the interpreter from ip, the compiled code is not used anymore
+    fn fallback(&mut self, ip: usize) -> Option<Interupt> {{
+        self.program.instruction_pointer = ip;
+        self.program.invalidate_cache();
+        self.interpreted = true;
+        None
+    }}
+}}
+
+// Dense memory is accessed directly, the Program methods are not inlined across crates
+fn read(program: &Program, addr: i64) -> i64 {{
+    match program.memory.get(addr as usize) {{
+        Some(value) => *value,
+        None => program.peek(addr as usize),
+    }}
+}}
+
+// addr was checked with Program::is_writable
+fn write(program: &mut Program, addr: i64, value: i64) {{
+    match program.memory.get_mut(addr as usize) {{
+        Some(cell) => *cell = value,
+        None => program.poke(addr as usize, value).unwrap(),
+    }}
+}}
+
+fn is_code(addr: i64) -> bool {{
+    CODE.get(addr as usize).copied().unwrap_or(false)
+}}
+
+fn is_writable(program: &Program, addr: i64) -> bool {{
+    (addr as usize) < program.memory.len() || program.is_writable(addr as usize)
+}}
+
+fn is_mapped(program: &Program, addr: i64) -> bool {{
+    (addr as usize) < program.memory.len() || program.is_mapped(addr as usize)
+}}
+"
+    )
+    .unwrap();
+
+    out
+}
+
+// Writes the code for one instruction, returns true if the basic block ends after it
+fn emit(out: &mut String, addr: usize, inst: &Instruction) -> bool {
+    let fallback = format!("return self.fallback({});", addr);
+    let indent = "                    ";
+
+    let mut lines = Vec::new();
+    // Loads the value of an input operand into a local
+    let load = |lines: &mut Vec<String>, name: &str, operand: &Operand| -> bool {
+        match operand {
+            Operand::Immediate(value) => lines.push(format!("let {} = {}i64;", name, value)),
+            Operand::Position(addr) if *addr < 0 => return false,
+            Operand::Position(addr) => {
+                lines.push(format!("let {} = read(&self.program, {});", name, addr))
+            }
+            Operand::Relative(offset) => {
+                lines.push(format!(
+                    "let Some({}_addr) = self.program.relative_base.checked_add({}) else {{ {} }};",
+                    name, offset, fallback
+                ));
+                lines.push(format!("if {}_addr < 0 {{ {} }}", name, fallback));
+                lines.push(format!(
+                    "let {} = read(&self.program, {}_addr);",
+                    name, name
+                ));
+            }
+        }
+        true
+    };
+    // Resolves the address of the output operand into `dest`
+    let store = |lines: &mut Vec<String>, operand: &Operand| -> bool {
+        match operand {
+            Operand::Immediate(_) => return false,
+            Operand::Position(addr) => lines.push(format!("let dest = {}i64;", addr)),
+            Operand::Relative(offset) => lines.push(format!(
+                "let Some(dest) = self.program.relative_base.checked_add({}) else {{ {} }};",
+                offset, fallback
+            )),
+        }
+        lines.push(format!(
+            "if dest < 0 || is_code(dest) || !is_writable(&self.program, dest) {{ {} }}",
+            fallback
+        ));
+        true
+    };
+
+    let next = addr + inst.size();
+    let ops = &inst.operands;
+    let mut ends_block = false;
+    let compiled = match inst.inst {
+        Inst::Add | Inst::Multiply | Inst::LessThan | Inst::Equals => {
+            let ok = load(&mut lines, "a", &ops[0])
+                && load(&mut lines, "b", &ops[1])
+                && store(&mut lines, &ops[2]);
+            match inst.inst {
+                // The interpreter faults on overflow
+                Inst::Add => lines.push(format!(
+                    "let Some(value) = a.checked_add(b) else {{ {} }};",
+                    fallback
+                )),
+                Inst::Multiply => lines.push(format!(
+                    "let Some(value) = a.checked_mul(b) else {{ {} }};",
+                    fallback
+                )),
+                Inst::LessThan => lines.push("let value = if a < b { 1 } else { 0 };".to_string()),
+                _ => lines.push("let value = if a == b { 1 } else { 0 };".to_string()),
+            }
+            lines.push("write(&mut self.program, dest, value);".to_string());
+            ok
+        }
+        Inst::Input => {
+            lines.push("let Some(value) = input.get(*consumed).copied() else {".to_string());
+            lines.push(format!("    self.program.instruction_pointer = {};", addr));
+            lines.push("    return Some(Interupt::Input);".to_string());
+            lines.push("};".to_string());
+            let ok = store(&mut lines, &ops[0]);
+            lines.push("write(&mut self.program, dest, value);".to_string());
+            lines.push("*consumed += 1;".to_string());
+            ok
+        }
+        Inst::Output => {
+            let ok = load(&mut lines, "a", &ops[0]);
+            lines.push(format!("self.program.instruction_pointer = {};", next));
+            lines.push("return Some(Interupt::Output(a));".to_string());
+            ends_block = true;
+            ok
+        }
+        Inst::JumpIfTrue | Inst::JumpIfFalse => {
+            let ok = load(&mut lines, "a", &ops[0]) && load(&mut lines, "b", &ops[1]);
+            let condition = if inst.inst == Inst::JumpIfTrue {
+                "a != 0"
+            } else {
+                "a == 0"
+            };
+            lines.push(format!("if {} {{", condition));
+            lines.push(format!(
+                "    if b < 0 || !is_mapped(&self.program, b) {{ {} }}",
+                fallback
+            ));
+            lines.push("    ip = b as usize;".to_string());
+            lines.push("} else {".to_string());
+            lines.push(format!("    ip = {};", next));
+            lines.push("}".to_string());
+            lines.push("continue;".to_string());
+            ends_block = true;
+            ok
+        }
+        Inst::AdjustRelativeBase => {
+            let ok = load(&mut lines, "a", &ops[0]);
+            lines.push(format!(
+                "let Some(relative_base) = self.program.relative_base.checked_add(a) else {{ {} }};",
+                fallback
+            ));
+            lines.push("self.program.relative_base = relative_base;".to_string());
+            ok
+        }
+        Inst::Halt => {
+            lines.push(format!("self.program.instruction_pointer = {};", addr));
+            lines.push("return Some(Interupt::Halt);".to_string());
+            ends_block = true;
+            true
+        }
+    };
+
+    if !compiled {
+        // The instruction always faults, the interpreter reports it
+        writeln!(out, "{}{}", indent, fallback).unwrap();
+        return true;
+    }
+
+    // Each instruction gets its own scope so locals can be reused
+    writeln!(out, "{}{{", indent).unwrap();
+    for line in lines {
+        writeln!(out, "{}    {}", indent, line).unwrap();
+    }
+    writeln!(out, "{}}}", indent).unwrap();
+
+    ends_block
+}
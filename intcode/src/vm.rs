@@ -0,0 +1,830 @@
+use crate::budget::Budget;
+use crate::error::{Fault, VmError};
+use crate::journal::Journal;
+use crate::memory::Pages;
+use crate::profile::Profile;
+use crate::selfmod::{CodePolicy, CodeWrite};
+use crate::session::Session;
+use crate::taint::Taint;
+use crate::trace::Tracer;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inst {
+    Add,                // 1
+    Multiply,           // 2
+    Input,              // 3
+    Output,             // 4
+    JumpIfTrue,         // 5
+    JumpIfFalse,        // 6
+    LessThan,           // 7
+    Equals,             // 8
+    AdjustRelativeBase, // 9
+    Halt,               // 99
+}
+
+impl Inst {
+    pub const ALL: [Inst; 10] = [
+        Inst::Add,
+        Inst::Multiply,
+        Inst::Input,
+        Inst::Output,
+        Inst::JumpIfTrue,
+        Inst::JumpIfFalse,
+        Inst::LessThan,
+        Inst::Equals,
+        Inst::AdjustRelativeBase,
+        Inst::Halt,
+    ];
+
+    pub fn code(&self) -> i64 {
+        match self {
+            Inst::Add => 1,
+            Inst::Multiply => 2,
+            Inst::Input => 3,
+            Inst::Output => 4,
+            Inst::JumpIfTrue => 5,
+            Inst::JumpIfFalse => 6,
+            Inst::LessThan => 7,
+            Inst::Equals => 8,
+            Inst::AdjustRelativeBase => 9,
+            Inst::Halt => 99,
+        }
+    }
+    pub fn params(&self) -> usize {
+        match self {
+            Inst::Add | Inst::Multiply | Inst::LessThan | Inst::Equals => 3,
+            Inst::JumpIfTrue | Inst::JumpIfFalse => 2,
+            Inst::Input | Inst::Output | Inst::AdjustRelativeBase => 1,
+            Inst::Halt => 0,
+        }
+    }
+    // Index of the parameter the instruction writes to
+    pub fn output_param(&self) -> Option<usize> {
+        match self {
+            Inst::Add | Inst::Multiply | Inst::LessThan | Inst::Equals => Some(2),
+            Inst::Input => Some(0),
+            _ => None,
+        }
+    }
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Inst::Add => "add",
+            Inst::Multiply => "mul",
+            Inst::Input => "in",
+            Inst::Output => "out",
+            Inst::JumpIfTrue => "jt",
+            Inst::JumpIfFalse => "jf",
+            Inst::LessThan => "lt",
+            Inst::Equals => "eq",
+            Inst::AdjustRelativeBase => "arb",
+            Inst::Halt => "hlt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interupt {
+    Input,
+    Output(i64),
+    Halt,
+    Fault(Fault),
+    // A watched address was accessed by the instruction at ip, for reads old and new are equal
+    Watch {
+        addr: usize,
+        old: i64,
+        new: i64,
+        ip: usize,
+    },
+    // The instruction budget ran out, see Program::set_run_budget and set_total_budget
+    BudgetExhausted,
+}
+
+impl Interupt {
+    // The interupt as Io, or the interupt itself for watchpoint hits and exhausted budgets
+    pub fn io(self) -> Result<Io, Interupt> {
+        match self {
+            Interupt::Input => Ok(Io::Input),
+            Interupt::Output(value) => Ok(Io::Output(value)),
+            Interupt::Halt => Ok(Io::Halt),
+            Interupt::Fault(fault) => Ok(Io::Fault(fault)),
+            Interupt::Watch { .. } | Interupt::BudgetExhausted => Err(self),
+        }
+    }
+    // Narrows the interupt for programs that never set watchpoints or an instruction budget,
+    // panics on anything else. Use Interupt::io when either can be set.
+    pub fn expect_io(self) -> Io {
+        match self.io() {
+            Ok(io) => io,
+            Err(Interupt::BudgetExhausted) => unreachable!("no budget is set"),
+            Err(_) => unreachable!("no watchpoints are set"),
+        }
+    }
+}
+
+// The interupts a program without watchpoints or an instruction budget can stop with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Io {
+    Input,
+    Output(i64),
+    Halt,
+    Fault(Fault),
+}
+
+impl fmt::Display for Io {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Io::Input => write!(f, "out of input"),
+            Io::Output(value) => write!(f, "output: {}", value),
+            Io::Halt => write!(f, "halt"),
+            Io::Fault(fault) => write!(f, "fault: {}", fault),
+        }
+    }
+}
+
+pub struct Opcode {
+    pub inst: Inst,
+    pub parameter_mode: u32,
+}
+
+pub struct ParameterMode {
+    pub mode: u8,
+    pub offset: usize,
+}
+
+impl Opcode {
+    pub fn from(code: i64) -> Result<Self, VmError> {
+        let inst = match code % 100 {
+            1 => Inst::Add,
+            2 => Inst::Multiply,
+            3 => Inst::Input,
+            4 => Inst::Output,
+            5 => Inst::JumpIfTrue,
+            6 => Inst::JumpIfFalse,
+            7 => Inst::LessThan,
+            8 => Inst::Equals,
+            9 => Inst::AdjustRelativeBase,
+            99 => Inst::Halt,
+            _ => return Err(VmError::UnknownOpcode),
+        };
+
+        Ok(Self {
+            inst,
+            parameter_mode: code as u32,
+        })
+    }
+    pub fn a(&self) -> ParameterMode {
+        ParameterMode {
+            mode: (self.parameter_mode / 100 % 10) as u8,
+            offset: 1,
+        }
+    }
+    pub fn b(&self) -> ParameterMode {
+        ParameterMode {
+            mode: (self.parameter_mode / 1000 % 10) as u8,
+            offset: 2,
+        }
+    }
+    pub fn c(&self) -> ParameterMode {
+        ParameterMode {
+            mode: (self.parameter_mode / 10000 % 10) as u8,
+            offset: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Flag {
+    #[default]
+    Unflagged,
+    Inst,
+    Param,
+    ReadWrite,
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub addrs: Range<usize>,
+    pub access: Access,
+}
+
+#[derive(Clone, Default)]
+pub struct Program {
+    pub memory: Vec<i64>,
+    pub memory_flags: Vec<Flag>,
+    pub instruction_pointer: usize,
+    pub relative_base: i64,
+    pub watchpoints: Vec<Watchpoint>,
+    // Watch interupt raised by the current instruction, returned once the instruction is done
+    pub(crate) watch_hit: Option<Interupt>,
+    pub(crate) journal: Option<Journal>,
+    pub(crate) profile: Option<Profile>,
+    pub(crate) taint: Option<Taint>,
+    pub(crate) trace: Option<Tracer>,
+    pub(crate) session: Option<Session>,
+    // Outputs of the frame run_until_frame is collecting
+    pub(crate) frame: Vec<i64>,
+    pub(crate) pages: Pages,
+    // Allocated cells, None for memory::DEFAULT_MEMORY_LIMIT
+    pub(crate) memory_limit: Option<usize>,
+    pub(crate) code_policy: CodePolicy,
+    pub(crate) code_writes: Vec<CodeWrite>,
+    pub(crate) budget: Budget,
+    pub(crate) executed: u64,
+    // Address of the halt instruction the program stopped at, polling it again does not count
+    pub(crate) halted: Option<usize>,
+    // Decoded instruction per address, cleared when one of its cells is written to
+    decoded: Vec<Option<Decoded>>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Operand {
+    mode: u8,
+    value: i64,
+}
+
+type Handler = fn(&mut Program, &Decoded, &mut InputCursor) -> Result<Option<Interupt>, VmError>;
+
+// Input of a call to Program::run or step. Reading only moves the cursor, the values that were
+// read are removed from the caller's Vec at once when the call returns.
+pub(crate) struct InputCursor<'a> {
+    values: &'a mut Vec<i64>,
+    read: usize,
+}
+
+impl<'a> InputCursor<'a> {
+    pub(crate) fn new(values: &'a mut Vec<i64>) -> Self {
+        Self { values, read: 0 }
+    }
+    fn peek(&self) -> Option<i64> {
+        self.values.get(self.read).copied()
+    }
+    fn advance(&mut self) {
+        self.read += 1;
+    }
+}
+
+impl Drop for InputCursor<'_> {
+    fn drop(&mut self) {
+        self.values.drain(..self.read);
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Decoded {
+    handler: Handler,
+    operands: [Operand; 3],
+}
+
+impl Program {
+    pub fn reset(&mut self, memory: Vec<i64>) {
+        self.memory = memory;
+        self.memory_flags = Vec::new();
+        self.memory_flags.resize(self.memory.len(), Flag::Unflagged);
+        self.instruction_pointer = 0;
+        self.relative_base = 0;
+        self.clear_pages();
+        self.code_writes.clear();
+        self.executed = 0;
+        self.halted = None;
+        self.watch_hit = None;
+        self.frame.clear();
+        self.decoded.clear();
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.restart();
+        }
+        if self.taint.is_some() {
+            self.taint = Some(Taint::default());
+        }
+        if self.session.is_some() {
+            self.start_session();
+        }
+    }
+    // Interupt with Interupt::Watch when an address in addrs is accessed
+    pub fn watch(&mut self, addrs: Range<usize>, access: Access) {
+        self.watchpoints.push(Watchpoint { addrs, access });
+    }
+    pub fn unwatch(&mut self, addr: usize) {
+        self.watchpoints
+            .retain(|watchpoint| !watchpoint.addrs.contains(&addr));
+    }
+    // Must be called after changing memory outside of Program::run
+    pub fn invalidate_cache(&mut self) {
+        self.decoded.clear();
+    }
+    pub(crate) fn invalidate(&mut self, addr: usize) {
+        for addr in addr.saturating_sub(3)..=addr {
+            if let Some(decoded) = self.decoded.get_mut(addr) {
+                *decoded = None;
+            }
+        }
+    }
+    fn param(&mut self, operand: Operand) -> Result<i64, VmError> {
+        let addr = match operand.mode {
+            0 /* Ptr to value */ => operand.value,
+            1 /* Litteral value */ => return Ok(operand.value),
+            2 /* Relative base */ => self.relative_address(operand.value)?,
+            mode => return Err(VmError::BadMode(mode)),
+        };
+
+        if addr < 0 {
+            return Err(VmError::NegativeAddress(addr));
+        }
+
+        let addr_usize = addr as usize;
+        if !self.is_mapped(addr_usize) {
+            return Ok(0);
+        }
+
+        let value = self.peek(addr_usize);
+        self.flag_read(addr_usize, value);
+        Ok(value)
+    }
+    fn write(&mut self, operand: Operand, value: i64) -> Result<(), VmError> {
+        let addr = match operand.mode {
+            0 /* Ptr to value */ => operand.value,
+            1 /* Litteral value */ => return Err(VmError::WriteInImmediateMode),
+            2 /* Relative base */ => self.relative_address(operand.value)?,
+            mode => return Err(VmError::BadMode(mode)),
+        };
+
+        if addr < 0 {
+            return Err(VmError::NegativeAddress(addr));
+        }
+
+        let addr_usize = addr as usize;
+        self.allocate(addr_usize)?;
+
+        self.flag_write(addr_usize, value)?;
+        let old = self.peek(addr_usize);
+        if let Some(journal) = &mut self.journal {
+            journal.write(addr_usize, old);
+        }
+        *self.cell_mut(addr_usize) = value;
+        Ok(())
+    }
+    fn relative_address(&self, offset: i64) -> Result<i64, VmError> {
+        self.relative_base
+            .checked_add(offset)
+            .ok_or(VmError::Overflow)
+    }
+    fn jump(&mut self, addr: i64) -> Result<(), VmError> {
+        if addr < 0 || !self.is_mapped(addr as usize) {
+            return Err(VmError::JumpOutOfBounds(addr));
+        }
+
+        self.instruction_pointer = addr as usize;
+        Ok(())
+    }
+    // Changes the flag of addr, the journal records the old one so it can be restored
+    fn set_flag(&mut self, addr: usize, flag: Flag) {
+        let Some(cell) = self.flag_mut(addr) else {
+            return;
+        };
+        let old = std::mem::replace(cell, flag);
+        if let Some(journal) = &mut self.journal {
+            journal.flag(addr, old);
+        }
+    }
+    fn flag(&mut self) {
+        match self.flag_mut(self.instruction_pointer) {
+            Some(Flag::Unflagged | Flag::Read | Flag::Write | Flag::ReadWrite) => {}
+            // Jumping into the middle of an instruction is valid intcode, keep the first flag
+            Some(Flag::Inst | Flag::Param) | None => return,
+        }
+        self.set_flag(self.instruction_pointer, Flag::Inst);
+    }
+    fn flag_param(&mut self, offset: usize) {
+        let addr = self.instruction_pointer + offset;
+        match self.flag_mut(addr) {
+            Some(Flag::Unflagged | Flag::Read | Flag::Write | Flag::ReadWrite) => {}
+            Some(Flag::Inst | Flag::Param) | None => return,
+        }
+        self.set_flag(addr, Flag::Param);
+    }
+    fn flag_read(&mut self, addr: usize, value: i64) {
+        self.check_watchpoints(addr, value, value, Access::Read);
+
+        let flag = match self.flag_mut(addr) {
+            Some(Flag::Write) => Flag::ReadWrite,
+            Some(Flag::Unflagged) => Flag::Read,
+            _ => return,
+        };
+        self.set_flag(addr, flag);
+    }
+    fn flag_write(&mut self, addr: usize, value: i64) -> Result<(), VmError> {
+        self.check_watchpoints(addr, self.peek(addr), value, Access::Write);
+
+        let flag = match self.flag_mut(addr) {
+            Some(Flag::Inst | Flag::Param) => {
+                // Self modifying code, the cached instruction is stale
+                self.code_write(addr, value)?;
+                self.invalidate(addr);
+                return Ok(());
+            }
+            Some(Flag::Read) => Flag::ReadWrite,
+            Some(Flag::Unflagged) => Flag::Write,
+            Some(Flag::Write | Flag::ReadWrite) | None => return Ok(()),
+        };
+        self.set_flag(addr, flag);
+        Ok(())
+    }
+    fn check_watchpoints(&mut self, addr: usize, old: i64, new: i64, access: Access) {
+        if self.watchpoints.is_empty() || self.watch_hit.is_some() {
+            return;
+        }
+
+        let hit = self.watchpoints.iter().any(|watchpoint| {
+            watchpoint.addrs.contains(&addr)
+                && (watchpoint.access == access || watchpoint.access == Access::ReadWrite)
+        });
+        if hit {
+            self.watch_hit = Some(Interupt::Watch {
+                addr,
+                old,
+                new,
+                ip: self.instruction_pointer,
+            });
+        }
+    }
+    pub fn run(&mut self, input: &mut Vec<i64>) -> Interupt {
+        let mut input = InputCursor::new(input);
+        let run_start = self.executed;
+        loop {
+            if self.run_budget_exhausted(run_start) {
+                return Interupt::BudgetExhausted;
+            }
+            if let Some(interupt) = self.step_cursor(&mut input) {
+                return interupt;
+            }
+        }
+    }
+    // Executes a single instruction, returns an interupt if the program should yield
+    pub fn step(&mut self, input: &mut Vec<i64>) -> Option<Interupt> {
+        self.step_cursor(&mut InputCursor::new(input))
+    }
+    fn step_cursor(&mut self, input: &mut InputCursor) -> Option<Interupt> {
+        // A watch hit by an instruction that also yielded is returned before executing the next one
+        if self.watch_hit.is_some() {
+            return self.watch_hit.take();
+        }
+        // Unless the halt was patched or undone, it already ran
+        let ip = self.instruction_pointer;
+        if self.halted == Some(ip) && self.peek(ip) % 100 == 99 {
+            return Some(Interupt::Halt);
+        }
+        if self.total_budget_exhausted() {
+            return Some(Interupt::BudgetExhausted);
+        }
+
+        // The journal, profile, taint tracking and trace are layered on top of exec in that order
+        if self.journal.is_some()
+            || self.profile.is_some()
+            || self.taint.is_some()
+            || self.trace.is_some()
+        {
+            return self.step_recorded(input);
+        }
+
+        let instruction_pointer = self.instruction_pointer;
+        let result = self.exec(input);
+        self.finish_step(instruction_pointer, result)
+    }
+    pub(crate) fn finish_step(
+        &mut self,
+        instruction_pointer: usize,
+        result: Result<Option<Interupt>, VmError>,
+    ) -> Option<Interupt> {
+        if let Ok(None | Some(Interupt::Output(_) | Interupt::Halt)) = result {
+            self.executed += 1;
+        }
+        if let Ok(Some(Interupt::Halt)) = result {
+            self.halted = Some(instruction_pointer);
+        }
+        match result {
+            Ok(None) if self.watch_hit.is_some() => self.watch_hit.take(),
+            Ok(None) => None,
+            Ok(interupt) => interupt,
+            Err(error) => {
+                // The instruction did not complete and will run again when resumed
+                self.watch_hit = None;
+                Some(Interupt::Fault(Fault {
+                    error,
+                    instruction_pointer,
+                    opcode: self.peek(instruction_pointer),
+                }))
+            }
+        }
+    }
+    // On error the instruction pointer is left on the faulting instruction
+    pub(crate) fn exec(&mut self, input: &mut InputCursor) -> Result<Option<Interupt>, VmError> {
+        let decoded = match self.decoded.get(self.instruction_pointer) {
+            Some(Some(decoded)) => *decoded,
+            _ => self.decode()?,
+        };
+
+        (decoded.handler)(self, &decoded, input)
+    }
+    // Decodes and flags the instruction at the instruction pointer and adds it to the cache
+    fn decode(&mut self) -> Result<Decoded, VmError> {
+        let opcode = Opcode::from(self.peek(self.instruction_pointer))?;
+
+        let handler: Handler = match opcode.inst {
+            Inst::Add => add,
+            Inst::Multiply => multiply,
+            Inst::Input => input,
+            Inst::Output => output,
+            Inst::JumpIfTrue => jump_if_true,
+            Inst::JumpIfFalse => jump_if_false,
+            Inst::LessThan => less_than,
+            Inst::Equals => equals,
+            Inst::AdjustRelativeBase => adjust_relative_base,
+            Inst::Halt => halt,
+        };
+
+        let mut operands = [Operand::default(); 3];
+        let params = [opcode.a(), opcode.b(), opcode.c()];
+        for (operand, param) in operands.iter_mut().zip(params).take(opcode.inst.params()) {
+            *operand = Operand {
+                mode: param.mode,
+                value: self.peek(self.instruction_pointer + param.offset),
+            };
+        }
+
+        self.flag();
+        for offset in 1..=opcode.inst.params() {
+            self.flag_param(offset);
+        }
+
+        let decoded = Decoded { handler, operands };
+        // Code in pages is decoded every time, the cache is as dense as memory
+        if self.instruction_pointer >= self.memory.len() {
+            return Ok(decoded);
+        }
+        if self.instruction_pointer >= self.decoded.len() {
+            self.decoded.resize(self.instruction_pointer + 1, None);
+        }
+        self.decoded[self.instruction_pointer] = Some(decoded);
+
+        Ok(decoded)
+    }
+}
+
+fn add(
+    program: &mut Program,
+    decoded: &Decoded,
+    _: &mut InputCursor,
+) -> Result<Option<Interupt>, VmError> {
+    let [a, b, c] = decoded.operands;
+    let value = program
+        .param(a)?
+        .checked_add(program.param(b)?)
+        .ok_or(VmError::Overflow)?;
+    program.write(c, value)?;
+    program.instruction_pointer += 4;
+    Ok(None)
+}
+
+fn multiply(
+    program: &mut Program,
+    decoded: &Decoded,
+    _: &mut InputCursor,
+) -> Result<Option<Interupt>, VmError> {
+    let [a, b, c] = decoded.operands;
+    let value = program
+        .param(a)?
+        .checked_mul(program.param(b)?)
+        .ok_or(VmError::Overflow)?;
+    program.write(c, value)?;
+    program.instruction_pointer += 4;
+    Ok(None)
+}
+
+fn input(
+    program: &mut Program,
+    decoded: &Decoded,
+    input: &mut InputCursor,
+) -> Result<Option<Interupt>, VmError> {
+    let Some(value) = input.peek() else {
+        return Ok(Some(Interupt::Input));
+    };
+
+    program.write(decoded.operands[0], value)?;
+    input.advance();
+    if let Some(journal) = &mut program.journal {
+        journal.input(value);
+    }
+    if let Some(session) = &mut program.session {
+        session.record(program.executed, value);
+    }
+    program.instruction_pointer += 2;
+    Ok(None)
+}
+
+fn output(
+    program: &mut Program,
+    decoded: &Decoded,
+    _: &mut InputCursor,
+) -> Result<Option<Interupt>, VmError> {
+    let a = program.param(decoded.operands[0])?;
+
+    program.instruction_pointer += 2;
+
+    Ok(Some(Interupt::Output(a)))
+}
+
+fn jump_if_true(
+    program: &mut Program,
+    decoded: &Decoded,
+    _: &mut InputCursor,
+) -> Result<Option<Interupt>, VmError> {
+    let [a, b, _] = decoded.operands;
+    let a = program.param(a)?;
+    let b = program.param(b)?;
+
+    if a != 0 {
+        program.jump(b)?;
+    } else {
+        program.instruction_pointer += 3;
+    }
+    Ok(None)
+}
+
+fn jump_if_false(
+    program: &mut Program,
+    decoded: &Decoded,
+    _: &mut InputCursor,
+) -> Result<Option<Interupt>, VmError> {
+    let [a, b, _] = decoded.operands;
+    if program.param(a)? == 0 {
+        let b = program.param(b)?;
+        program.jump(b)?;
+    } else {
+        program.instruction_pointer += 3;
+    }
+    Ok(None)
+}
+
+fn less_than(
+    program: &mut Program,
+    decoded: &Decoded,
+    _: &mut InputCursor,
+) -> Result<Option<Interupt>, VmError> {
+    let [a, b, c] = decoded.operands;
+    let a = program.param(a)?;
+    let b = program.param(b)?;
+    let value = if a < b { 1 } else { 0 };
+    program.write(c, value)?;
+    program.instruction_pointer += 4;
+    Ok(None)
+}
+
+fn equals(
+    program: &mut Program,
+    decoded: &Decoded,
+    _: &mut InputCursor,
+) -> Result<Option<Interupt>, VmError> {
+    let [a, b, c] = decoded.operands;
+    let a = program.param(a)?;
+    let b = program.param(b)?;
+    let value = if a == b { 1 } else { 0 };
+    program.write(c, value)?;
+    program.instruction_pointer += 4;
+    Ok(None)
+}
+
+fn adjust_relative_base(
+    program: &mut Program,
+    decoded: &Decoded,
+    _: &mut InputCursor,
+) -> Result<Option<Interupt>, VmError> {
+    let a = program.param(decoded.operands[0])?;
+
+    program.relative_base = program.relative_address(a)?;
+    program.instruction_pointer += 2;
+    Ok(None)
+}
+
+fn halt(_: &mut Program, _: &Decoded, _: &mut InputCursor) -> Result<Option<Interupt>, VmError> {
+    Ok(Some(Interupt::Halt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expect_io() {
+        let mut program = Program::default();
+        program.reset(vec![4, 0, 99]);
+        assert_eq!(program.run(&mut vec![]).expect_io(), Io::Output(4));
+        assert_eq!(program.run(&mut vec![]).expect_io(), Io::Halt);
+    }
+
+    #[test]
+    fn io() {
+        let mut program = Program::default();
+        program.reset(vec![1105, 1, 0]);
+        program.set_run_budget(10);
+        assert_eq!(
+            program.run(&mut vec![]).io(),
+            Err(Interupt::BudgetExhausted)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "no budget is set")]
+    fn expect_io_with_budget() {
+        let mut program = Program::default();
+        program.reset(vec![1105, 1, 0]);
+        program.set_run_budget(10);
+        program.run(&mut vec![]).expect_io();
+    }
+
+    #[test]
+    fn input_is_drained_once() {
+        let mut program = Program::default();
+        // Reads two values and outputs their sum
+        program.reset(vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0]);
+        let mut input = vec![2, 3, 4];
+        assert_eq!(program.run(&mut input), Interupt::Output(5));
+        assert_eq!(input, vec![4]);
+
+        // A faulting input instruction does not consume its value
+        program.reset(vec![3, -1, 99]);
+        assert!(matches!(program.run(&mut input), Interupt::Fault(_)));
+        assert_eq!(input, vec![4]);
+    }
+
+    #[test]
+    fn halt_counts_once() {
+        let mut program = Program::default();
+        program.reset(vec![1101, 1, 2, 5, 99, 0]);
+        program.set_total_budget(2);
+        for _ in 0..3 {
+            assert_eq!(program.run(&mut vec![]), Interupt::Halt);
+        }
+        assert_eq!(program.executed(), 2);
+        assert_eq!(program.remaining(), Some(0));
+
+        // A patched halt runs the new instruction
+        program.poke(4, 104).unwrap();
+        program.invalidate_cache();
+        program.clear_budgets();
+        assert_eq!(program.run(&mut vec![]), Interupt::Output(3));
+    }
+
+    #[test]
+    fn run_to_end() {
+        // Outputs every input doubled until it runs out of input
+        let mut program = Program::default();
+        program.reset(vec![3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0]);
+        let mut input = vec![1, 2, 3];
+        let (outputs, end) = crate::run_to_end(&mut program, &mut input);
+        assert_eq!((outputs, end), (vec![2, 4, 6], Io::Input));
+        assert_eq!(end.to_string(), "out of input");
+    }
+
+    #[test]
+    fn overflow_faults() {
+        let mut program = Program::default();
+        program.reset(vec![1101, i64::MAX, 1, 0, 99]);
+        let Interupt::Fault(fault) = program.run(&mut vec![]) else {
+            panic!("expected a fault");
+        };
+        assert_eq!(fault.error, VmError::Overflow);
+        assert_eq!(fault.instruction_pointer, 0);
+
+        program.reset(vec![109, i64::MAX, 109, 1, 99]);
+        let Interupt::Fault(fault) = program.run(&mut vec![]) else {
+            panic!("expected a fault");
+        };
+        assert_eq!(
+            (fault.error, fault.instruction_pointer),
+            (VmError::Overflow, 2)
+        );
+        assert_eq!(program.relative_base, i64::MAX);
+
+        program.reset(vec![109, -1, 1202, i64::MIN, 2, 0, 99]);
+        let Interupt::Fault(fault) = program.run(&mut vec![]) else {
+            panic!("expected a fault");
+        };
+        assert_eq!(
+            (fault.error, fault.instruction_pointer),
+            (VmError::Overflow, 2)
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use intcode::{Interupt, Program};
+
+// Regenerate with:
+// cargo run --bin transpile -- tests/transpiled/program.txt Compiled > tests/transpiled/compiled.rs
+#[rustfmt::skip]
+#[path = "transpiled/compiled.rs"]
+mod compiled;
+
+const PROGRAM: &str = include_str!("transpiled/program.txt");
+
+#[test]
+fn compiled_is_up_to_date() {
+    let source = intcode::transpile::transpile(&intcode::parse(PROGRAM), "Compiled");
+    assert_eq!(source, include_str!("transpiled/compiled.rs"));
+
+    let program = intcode::parse(include_str!("../benches/transpiled/program.txt"));
+    let source = intcode::transpile::transpile(&program, "Compiled");
+    assert_eq!(source, include_str!("../benches/transpiled/compiled.rs"));
+}
+
+// Runs until halt, feeding one value on every input request, and returns every interupt
+fn run(mut next: impl FnMut(&mut Vec<i64>) -> Interupt, inputs: &[i64]) -> Vec<Interupt> {
+    let mut inputs = inputs.iter();
+    let mut input = Vec::new();
+    let mut interupts = Vec::new();
+    loop {
+        let interupt = next(&mut input);
+        interupts.push(interupt);
+        match interupt {
+            Interupt::Input => match inputs.next() {
+                Some(value) => input.push(*value),
+                None => break,
+            },
+            Interupt::Output(_) => {}
+            _ => break,
+        }
+    }
+    interupts
+}
+
+#[test]
+fn matches_interpreter() {
+    // The last input decides if the program patches its own code, which the compiled code
+    // leaves to the interpreter
+    // The factorial of 25 overflows, which faults in both
+    for inputs in [[5, 0], [5, 1], [0, 1], [-3, 0], [25, 0]] {
+        let mut program = Program::default();
+        program.reset(intcode::parse(PROGRAM));
+        let mut compiled = compiled::Compiled::default();
+
+        let expected = run(|input| program.run(input), &inputs);
+        let actual = run(|input| compiled.run(input), &inputs);
+        assert_eq!(actual, expected, "inputs {:?}", inputs);
+
+        let cells = program.allocated().max(compiled.program.allocated());
+        for addr in 0..cells {
+            assert_eq!(
+                compiled.program.peek(addr),
+                program.peek(addr),
+                "cell {}",
+                addr
+            );
+        }
+        assert_eq!(compiled.program.relative_base, program.relative_base);
+    }
+}
@@ -0,0 +1,217 @@
+// Generated by intcode::transpile, do not edit
+//
+// The compiled code does not count executed instructions or enforce instruction budgets,
+// does not check watchpoints and does not update memory_flags, the journal, profile, taint,
+// trace or session of the wrapped Program. All of them work again once the interpreter takes
+// over after a fallback.
+#![allow(clippy::all, unused)]
+
+use intcode::{Interupt, Program};
+
+const MEMORY: [i64; 38] = [3, 35, 2, 36, 35, 36, 1001, 35, -1, 35, 109, 1, 4, 36, 204, 36, 107, 0, 35, 37, 1005, 37, 2, 3, 35, 1006, 35, 34, 1101, 0, 104, 32, 99, 7, 99, 0, 1, 0];
+
+// Cells that belong to compiled instructions
+const CODE: [bool; 38] = [true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, false, true, false, false, false];
+
+pub struct Compiled {
+    pub program: Program,
+    interpreted: bool,
+}
+
+impl Default for Compiled {
+    fn default() -> Self {
+        let mut program = Program::default();
+        program.reset(MEMORY.to_vec());
+        Self {
+            program,
+            interpreted: false,
+        }
+    }
+}
+
+impl Compiled {
+    pub fn run(&mut self, input: &mut Vec<i64>) -> Interupt {
+        if !self.interpreted {
+            // Like Program::run, read input is removed once when the compiled code returns
+            let mut consumed = 0;
+            let interupt = self.run_compiled(input, &mut consumed);
+            input.drain(..consumed);
+            if let Some(interupt) = interupt {
+                return interupt;
+            }
+        }
+        self.program.run(input)
+    }
+    // Returns None when the interpreter has to take over
+    fn run_compiled(&mut self, input: &[i64], consumed: &mut usize) -> Option<Interupt> {
+        let mut ip = self.program.instruction_pointer;
+        loop {
+            match ip {
+                0 => {
+                    // 0000: IN -> [35]
+                    {
+                        let Some(value) = input.get(*consumed).copied() else {
+                            self.program.instruction_pointer = 0;
+                            return Some(Interupt::Input);
+                        };
+                        let dest = 35i64;
+                        if dest < 0 || is_code(dest) || !is_writable(&self.program, dest) { return self.fallback(0); }
+                        write(&mut self.program, dest, value);
+                        *consumed += 1;
+                    }
+                    ip = 2;
+                    continue;
+                }
+                2 => {
+                    // 0002: MUL [36], [35] -> [36]
+                    {
+                        let a = read(&self.program, 36);
+                        let b = read(&self.program, 35);
+                        let dest = 36i64;
+                        if dest < 0 || is_code(dest) || !is_writable(&self.program, dest) { return self.fallback(2); }
+                        let Some(value) = a.checked_mul(b) else { return self.fallback(2); };
+                        write(&mut self.program, dest, value);
+                    }
+                    // 0006: ADD [35], #-1 -> [35]
+                    {
+                        let a = read(&self.program, 35);
+                        let b = -1i64;
+                        let dest = 35i64;
+                        if dest < 0 || is_code(dest) || !is_writable(&self.program, dest) { return self.fallback(6); }
+                        let Some(value) = a.checked_add(b) else { return self.fallback(6); };
+                        write(&mut self.program, dest, value);
+                    }
+                    // 0010: ARB #1
+                    {
+                        let a = 1i64;
+                        let Some(relative_base) = self.program.relative_base.checked_add(a) else { return self.fallback(10); };
+                        self.program.relative_base = relative_base;
+                    }
+                    // 0012: OUT [36]
+                    {
+                        let a = read(&self.program, 36);
+                        self.program.instruction_pointer = 14;
+                        return Some(Interupt::Output(a));
+                    }
+                }
+                14 => {
+                    // 0014: OUT [rb+36]
+                    {
+                        let Some(a_addr) = self.program.relative_base.checked_add(36) else { return self.fallback(14); };
+                        if a_addr < 0 { return self.fallback(14); }
+                        let a = read(&self.program, a_addr);
+                        self.program.instruction_pointer = 16;
+                        return Some(Interupt::Output(a));
+                    }
+                }
+                16 => {
+                    // 0016: LT #0, [35] -> [37]
+                    {
+                        let a = 0i64;
+                        let b = read(&self.program, 35);
+                        let dest = 37i64;
+                        if dest < 0 || is_code(dest) || !is_writable(&self.program, dest) { return self.fallback(16); }
+                        let value = if a < b { 1 } else { 0 };
+                        write(&mut self.program, dest, value);
+                    }
+                    // 0020: JT [37], #2
+                    {
+                        let a = read(&self.program, 37);
+                        let b = 2i64;
+                        if a != 0 {
+                            if b < 0 || !is_mapped(&self.program, b) { return self.fallback(20); }
+                            ip = b as usize;
+                        } else {
+                            ip = 23;
+                        }
+                        continue;
+                    }
+                }
+                23 => {
+                    // 0023: IN -> [35]
+                    {
+                        let Some(value) = input.get(*consumed).copied() else {
+                            self.program.instruction_pointer = 23;
+                            return Some(Interupt::Input);
+                        };
+                        let dest = 35i64;
+                        if dest < 0 || is_code(dest) || !is_writable(&self.program, dest) { return self.fallback(23); }
+                        write(&mut self.program, dest, value);
+                        *consumed += 1;
+                    }
+                    // 0025: JF [35], #34
+                    {
+                        let a = read(&self.program, 35);
+                        let b = 34i64;
+                        if a == 0 {
+                            if b < 0 || !is_mapped(&self.program, b) { return self.fallback(25); }
+                            ip = b as usize;
+                        } else {
+                            ip = 28;
+                        }
+                        continue;
+                    }
+                }
+                28 => {
+                    // 0028: ADD #0, #104 -> [32]
+                    {
+                        let a = 0i64;
+                        let b = 104i64;
+                        let dest = 32i64;
+                        if dest < 0 || is_code(dest) || !is_writable(&self.program, dest) { return self.fallback(28); }
+                        let Some(value) = a.checked_add(b) else { return self.fallback(28); };
+                        write(&mut self.program, dest, value);
+                    }
+                    // 0032: HLT
+                    {
+                        self.program.instruction_pointer = 32;
+                        return Some(Interupt::Halt);
+                    }
+                }
+                34 => {
+                    // 0034: HLT
+                    {
+                        self.program.instruction_pointer = 34;
+                        return Some(Interupt::Halt);
+                    }
+                }
+                _ => return self.fallback(ip),
+            }
+        }
+    }
+    // Continue in the interpreter from ip, the compiled code is not used anymore
+    fn fallback(&mut self, ip: usize) -> Option<Interupt> {
+        self.program.instruction_pointer = ip;
+        self.program.invalidate_cache();
+        self.interpreted = true;
+        None
+    }
+}
+
+// Dense memory is accessed directly, the Program methods are not inlined across crates
+fn read(program: &Program, addr: i64) -> i64 {
+    match program.memory.get(addr as usize) {
+        Some(value) => *value,
+        None => program.peek(addr as usize),
+    }
+}
+
+// addr was checked with Program::is_writable
+fn write(program: &mut Program, addr: i64, value: i64) {
+    match program.memory.get_mut(addr as usize) {
+        Some(cell) => *cell = value,
+        None => program.poke(addr as usize, value).unwrap(),
+    }
+}
+
+fn is_code(addr: i64) -> bool {
+    CODE.get(addr as usize).copied().unwrap_or(false)
+}
+
+fn is_writable(program: &Program, addr: i64) -> bool {
+    (addr as usize) < program.memory.len() || program.is_writable(addr as usize)
+}
+
+fn is_mapped(program: &Program, addr: i64) -> bool {
+    (addr as usize) < program.memory.len() || program.is_mapped(addr as usize)
+}